@@ -0,0 +1,51 @@
+use dashmap::DashMap;
+use std::hash::Hash;
+
+/// A cache holding at most `capacity` entries. It is cleared once full, which
+/// is cheaper than tracking usage and good enough for per-word lookups since
+/// the common words of a document are back after a single pass.
+pub struct BoundedCache<K: Eq + Hash, V: Clone> {
+    entries: DashMap<K, V>,
+    capacity: usize,
+}
+
+impl<K: Eq + Hash, V: Clone> BoundedCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: DashMap::new(),
+            capacity,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.entries.get(key).map(|v| v.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if self.entries.len() >= self.capacity {
+            self.entries.clear();
+        }
+        self.entries.insert(key, value);
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_never_holds_more_than_its_capacity() {
+        let cache = BoundedCache::new(2);
+        cache.insert("helo", false);
+        cache.insert("hello", true);
+        assert_eq!(cache.get(&"helo"), Some(false));
+
+        cache.insert("world", true);
+        assert_eq!(cache.get(&"helo"), None);
+        assert_eq!(cache.get(&"world"), Some(true));
+    }
+}
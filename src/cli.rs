@@ -0,0 +1,114 @@
+use crate::report::{format_misspelling, Finding};
+use crate::workspace;
+use crate::Backend;
+use crop::Rope;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tower_lsp::lsp_types::Url;
+use tower_lsp::LspService;
+
+/// Suggestions listed for every finding in JSON output
+const REPORTED_SUGGESTIONS: usize = 3;
+/// Answers are awaited for longer than in the editor, a word that times out
+/// counts as correct and would let a misspelling pass
+const MIN_OPERATION_TIMEOUT_MS: u64 = 60_000;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum OutputFormat {
+    /// A `path:line:column: message` line per misspelling
+    #[default]
+    Text,
+    /// An array of findings with their suggestions
+    Json,
+}
+
+/// Spell checks `paths` without an editor and prints every misspelling.
+/// Directories are checked recursively.
+/// Fails if a misspelling is found or a file can't be read.
+pub async fn check(paths: &[PathBuf], format: OutputFormat) -> ExitCode {
+    // The client isn't connected, messages sent to it are dropped
+    let (service, _) = LspService::new(Backend::new);
+    let backend = service.inner();
+    let root = std::env::current_dir().ok();
+    *backend.workspace_folders.write() = root.iter().cloned().collect();
+    *backend.workspace_root.write() = root;
+    backend.apply_settings(None).await;
+    {
+        let mut config = backend.config.write();
+        config.operation_timeout_ms = config.operation_timeout_ms.max(MIN_OPERATION_TIMEOUT_MS);
+    }
+    backend.load_local_dict_from_file();
+    backend.start_spellchecker().await;
+
+    let tab_width = { backend.config.read().tab_width };
+    let mut failed = false;
+    let mut findings = vec![];
+    for path in files(backend, paths) {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{}: {e}", path.display());
+                failed = true;
+                continue;
+            }
+        };
+        let code = Rope::from(text);
+        let folder = uri(&path).and_then(|uri| backend.folder_of(&uri));
+        for token in backend.misspelled_tokens(&code, "", folder.as_deref()) {
+            let line = code.line(token.start.line as usize).to_string();
+            let path = path.to_string_lossy();
+            match format {
+                OutputFormat::Text => {
+                    println!("{}", format_misspelling(&path, &line, &token, tab_width))
+                }
+                OutputFormat::Json => {
+                    let mut suggestions = backend.suggest(&token.lexeme, folder.as_deref());
+                    suggestions.truncate(REPORTED_SUGGESTIONS);
+                    findings.push(Finding::new(&path, &line, &token, tab_width, suggestions));
+                }
+            }
+            failed = true;
+        }
+    }
+    if let OutputFormat::Json = format {
+        match serde_json::to_string(&findings) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("Unable to serialize findings: {e}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Files given by name are always checked, files found in directories only
+/// if `include_files` and `ignore_files` allow it
+fn files(backend: &Backend, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let respect_gitignore = { backend.config.read().respect_gitignore };
+    paths
+        .iter()
+        .flat_map(|path| {
+            if !path.is_dir() {
+                return vec![path.to_owned()];
+            }
+            workspace::files(path, respect_gitignore)
+                .into_iter()
+                .filter(|file| is_checked_file(backend, file))
+                .collect()
+        })
+        .collect()
+}
+
+fn is_checked_file(backend: &Backend, path: &Path) -> bool {
+    uri(path).is_none_or(|uri| backend.is_checked_file(&uri))
+}
+
+fn uri(path: &Path) -> Option<Url> {
+    Url::from_file_path(std::path::absolute(path).ok()?).ok()
+}
@@ -1,20 +1,39 @@
 use dirs::{config_dir, data_dir};
-use log::info;
-use reqwest::get;
+use log::{error, info, warn};
 use serde;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
-use tower_lsp::lsp_types::DiagnosticSeverity;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Dictionary {
     pub language: String,
     pub aff: String,
     pub dic: String,
+    /// Name of the cache directory, derived from the urls when absent
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Severity of the words this dictionary rejects, even if another one
+    /// knows them. The first rejecting dictionary with a severity decides,
+    /// without one words are only reported when every dictionary rejects them.
+    #[serde(default)]
+    pub severity: Option<ConfigDiagnosticSeverity>,
+    /// Expected SHA-256 of the downloaded files, hex encoded
+    #[serde(default)]
+    pub aff_sha256: Option<String>,
+    #[serde(default)]
+    pub dic_sha256: Option<String>,
 }
 
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+const DOWNLOAD_RETRY_DELAY: Duration = Duration::from_millis(500);
+
 #[derive(Clone)]
 pub struct DictionaryPath {
     pub aff: PathBuf,
@@ -27,39 +46,184 @@ impl Dictionary {
             language: language.to_string(),
             aff: aff.to_string(),
             dic: dic.to_string(),
+            version: None,
+            severity: None,
+            aff_sha256: None,
+            dic_sha256: None,
         }
     }
-    pub async fn resolve(&self) -> DictionaryPath {
-        let data_dir = data_dir()
+
+    /// Fails if a file couldn't be downloaded within `download_timeout`,
+    /// even after retrying
+    pub async fn resolve(&self, download_timeout: Duration) -> Result<DictionaryPath, String> {
+        Ok(DictionaryPath {
+            aff: self
+                .resolve_file(&self.aff, "index.aff", &self.aff_sha256, download_timeout)
+                .await?,
+            dic: self
+                .resolve_file(&self.dic, "index.dic", &self.dic_sha256, download_timeout)
+                .await?,
+        })
+    }
+
+    /// Local files are used where they are, urls are downloaded to the cache
+    async fn resolve_file(
+        &self,
+        location: &str,
+        file_name: &str,
+        sha256: &Option<String>,
+        download_timeout: Duration,
+    ) -> Result<PathBuf, String> {
+        if let Some(path) = local_path(location) {
+            return Ok(path);
+        }
+        let base_dir = data_dir()
             .expect("Could not find data dir")
-            .join("rustproof")
-            .join(&self.language);
+            .join("rustproof");
+        let data_dir = self.cache_dir(&base_dir);
         ensure_directory(&data_dir).await;
-        let aff_path = data_dir.join("index.aff");
-        let dic_path = data_dir.join("index.dic");
-        let aff = self.aff.clone();
-        let dic = self.dic.clone();
-        Dictionary::download_if_not_exists(&aff_path, &aff).await;
-        Dictionary::download_if_not_exists(&dic_path, &dic).await;
-        DictionaryPath {
-            aff: aff_path,
-            dic: dic_path,
+        remove_stale_versions(&self.source_dir(&base_dir), &self.cache_version()).await;
+        let path = data_dir.join(file_name);
+        let download = Download {
+            url: location,
+            sha256: sha256.as_deref(),
+            timeout: download_timeout,
+        };
+        download_if_not_exists(&path, &download).await?;
+        if file_name == "index.dic" && !is_dic_file(&path).await {
+            error!("{location} is not a dictionary, .dic files start with their word count");
         }
+        Ok(path)
     }
 
-    async fn download_if_not_exists(buf: &PathBuf, url: &str) {
-        if buf.exists() {
-            return;
-        };
-        info!("{:?}", buf);
-        let mut file = fs::File::create(&buf).await.expect("Could not create file");
-        let response = get(url).await.expect("Unable to send request");
-        let bytes = response.bytes().await.expect("Unable to get bytes");
-        file.write_all(&bytes).await.expect("Unable to write file");
+    /// Removes the downloaded files so the next `resolve` fetches them again,
+    /// local files are left alone
+    pub async fn clear_cache(&self) {
+        let base_dir = data_dir()
+            .expect("Could not find data dir")
+            .join("rustproof");
+        let data_dir = self.cache_dir(&base_dir);
+        for (location, file_name) in [(&self.aff, "index.aff"), (&self.dic, "index.dic")] {
+            if local_path(location).is_some() {
+                continue;
+            }
+            let path = data_dir.join(file_name);
+            if let Err(e) = fs::remove_file(&path).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Unable to remove cached dictionary {:?}: {e}", path);
+                }
+            }
+        }
+    }
+
+    /// Files are cached in `<base_dir>/<language>/<source>/<version>/`, the
+    /// source being a hash of the urls, so changing the urls or the version
+    /// fetches fresh files
+    pub fn cache_dir(&self, base_dir: &Path) -> PathBuf {
+        self.source_dir(base_dir).join(self.cache_version())
+    }
+
+    /// Versions of this dictionary are cached here, dictionaries sharing a
+    /// language have their own so they don't remove each other's files
+    fn source_dir(&self, base_dir: &Path) -> PathBuf {
+        let source = fnv1a(format!("{}\n{}", self.aff, self.dic).as_bytes());
+        base_dir.join(&self.language).join(format!("{source:016x}"))
+    }
+
+    pub fn cache_version(&self) -> String {
+        self.version.clone().unwrap_or_else(|| "latest".to_string())
     }
 }
 
-#[derive(Deserialize, Clone)]
+struct Download<'a> {
+    url: &'a str,
+    /// Expected SHA-256 of the file, unchecked when absent
+    sha256: Option<&'a str>,
+    timeout: Duration,
+}
+
+/// Downloads to `path` unless a file with the expected checksum is already
+/// there, failed attempts are retried with a growing delay
+async fn download_if_not_exists(path: &Path, download: &Download<'_>) -> Result<(), String> {
+    let url = download.url;
+    match download.sha256 {
+        // Without a checksum there is nothing to verify, the cached file isn't read
+        None => {
+            if fs::try_exists(path).await.unwrap_or(false) {
+                return Ok(());
+            }
+        }
+        Some(sha256) => {
+            if let Ok(cached) = fs::read(path).await {
+                match verify_sha256(&cached, Some(sha256)) {
+                    Ok(()) => return Ok(()),
+                    Err(e) => {
+                        warn!("Downloading {url} again, the cached file is corrupt: {e}");
+                        let _ = fs::remove_file(path).await;
+                    }
+                }
+            }
+        }
+    }
+    info!("Downloading {url} to {:?}", path);
+    let mut attempt = 1;
+    loop {
+        match fetch(path, download).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < DOWNLOAD_ATTEMPTS => {
+                warn!("Attempt {attempt} to download {url} failed: {e}");
+                tokio::time::sleep(DOWNLOAD_RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(format!("Unable to download {url}: {e}")),
+        }
+    }
+}
+
+/// The file is written once the whole response arrived and matches its
+/// checksum, a failed download leaves nothing behind so the next start
+/// tries again
+async fn fetch(path: &Path, download: &Download<'_>) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(download.timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(download.url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    verify_sha256(&bytes, download.sha256)?;
+    let tmp = path.with_extension("part");
+    let written = match fs::write(&tmp, &bytes).await {
+        Ok(()) => fs::rename(&tmp, path).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+fn verify_sha256(bytes: &[u8], expected: Option<&str>) -> Result<(), String> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let actual = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(format!("expected SHA-256 {expected}, got {actual}"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")] // Ensures case-insensitivity
 pub enum ConfigDiagnosticSeverity {
     Error,
@@ -79,30 +243,477 @@ impl ConfigDiagnosticSeverity {
     }
 }
 
-#[derive(Deserialize, Clone)]
+/// How diagnostics are rendered, independent of their severity
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticPresentation {
+    /// Reported with `diagnostic_severity`, most editors underline the word
+    Underline,
+    /// Reported as an unnecessary hint, which editors render as a subtle
+    /// marker instead of an underline. Quick fixes stay available.
+    Subtle,
+}
+
+/// How suggestions with the same edit distance and length difference to the
+/// misspelled word are ordered
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionTiebreak {
+    /// Most frequent word first according to `frequency_list`,
+    /// alphabetical for words missing from the list
+    Frequency,
+    Alphabetical,
+    /// Keep the order the dictionaries returned them in
+    Original,
+}
+
+/// What happens to capitalized suggestions, usually proper nouns,
+/// for words that are lowercase
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProperNounSuggestions {
+    Keep,
+    /// Rank them after all other suggestions
+    Demote,
+    Remove,
+}
+
+/// What is re-checked when a document changes
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckMode {
+    /// The whole document
+    File,
+    /// Only the words touched by an edit, as long as it stays on one line
+    Word,
+}
+
+/// Which parts of a document are spell-checked
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckScope {
+    All,
+    /// Only comments and strings, including heredocs and raw strings.
+    /// Languages rustproof can't parse are checked entirely.
+    CommentsStrings,
+}
+
+/// Where code action and hover suggestions come from, words are always
+/// checked by Hunspell
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionEngine {
+    #[default]
+    Hunspell,
+    /// Symmetric delete lookup of the dictionary words, much faster but
+    /// it doesn't suggest inflected forms the dictionary builds from affixes
+    Symspell,
+}
+
+/// Spelling of the English dictionary in the default `dictionaries`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum LanguageVariant {
+    #[default]
+    #[serde(rename = "en-US")]
+    EnUs,
+    #[serde(rename = "en-GB")]
+    EnGb,
+}
+
+impl LanguageVariant {
+    /// The English dictionary of the variant, `None` keeps the default one
+    fn dictionary(self) -> Option<Dictionary> {
+        match self {
+            LanguageVariant::EnUs => None,
+            LanguageVariant::EnGb => Some(Dictionary::new(
+                "en",
+                &(WOOORM_BASE_URL.to_string() + "/en-GB/index.aff"),
+                &(WOOORM_BASE_URL.to_string() + "/en-GB/index.dic"),
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
     #[serde(default = "default_dict_path")]
     pub dict_path: PathBuf,
+    /// Dictionary shared by everyone working on the project, relative to the
+    /// workspace root
+    #[serde(default = "default_workspace_dict_path")]
+    pub workspace_dict_path: PathBuf,
     #[serde(default = "default_dictionaries")]
     pub dictionaries: Vec<Dictionary>,
+    /// Named groups of dictionaries, referred to by their `language`
+    #[serde(default)]
+    pub dictionary_groups: HashMap<String, Vec<String>>,
+    /// Group used for languages without an entry in `language_groups`
+    #[serde(default)]
+    pub default_group: Option<String>,
+    /// LSP languageId -> dictionary group
+    #[serde(default)]
+    pub language_groups: HashMap<String, String>,
     #[serde(default = "default_diagnostic_severity")]
     pub diagnostic_severity: ConfigDiagnosticSeverity,
+    #[serde(default = "default_diagnostic_presentation")]
+    pub diagnostic_presentation: DiagnosticPresentation,
+    #[serde(default = "default_ignore_markdown_links")]
+    pub ignore_markdown_links: bool,
+    #[serde(default = "default_suggestion_tiebreak")]
+    pub suggestion_tiebreak: SuggestionTiebreak,
+    #[serde(default = "default_proper_noun_suggestions")]
+    pub proper_noun_suggestions: ProperNounSuggestions,
+    #[serde(default = "default_max_suggestions")]
+    pub max_suggestions: usize,
+    #[serde(default)]
+    pub eager_suggestions: bool,
+    #[serde(default = "default_suggestion_budget_ms")]
+    pub suggestion_budget_ms: u64,
+    #[serde(default)]
+    pub frequency_list: Option<PathBuf>,
+    #[serde(default)]
+    pub include_files: Vec<String>,
+    #[serde(default)]
+    pub ignore_files: Vec<String>,
+    #[serde(default)]
+    pub ignore_regex: Vec<String>,
+    #[serde(default = "default_respect_gitignore")]
+    pub respect_gitignore: bool,
+    #[serde(default)]
+    pub add_all_immediately: bool,
+    #[serde(default = "default_check_mode")]
+    pub check_mode: CheckMode,
+    #[serde(default)]
+    pub suppress_while_typing: bool,
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    #[serde(default)]
+    pub group_by_word: bool,
+    #[serde(default = "default_check_scope")]
+    pub check_scope: CheckScope,
+    #[serde(default = "default_operation_timeout_ms")]
+    pub operation_timeout_ms: u64,
+    #[serde(default = "default_download_timeout_ms")]
+    pub download_timeout_ms: u64,
+    /// Threads checking words in parallel, they share the dictionaries
+    #[serde(default = "default_checker_threads")]
+    pub checker_threads: usize,
+    #[serde(default = "default_ignore_ordinals")]
+    pub ignore_ordinals: bool,
+    #[serde(default = "default_min_word_length")]
+    pub min_word_length: usize,
+    #[serde(default)]
+    pub check_short_acronyms: bool,
+    #[serde(default = "default_prose_annotations")]
+    pub prose_annotations: Vec<String>,
+    #[serde(default = "default_skip_format_placeholders")]
+    pub skip_format_placeholders: bool,
+    #[serde(default = "default_clear_on_close")]
+    pub clear_on_close: bool,
+    /// Columns a tab is expanded to in human-readable reports,
+    /// positions sent to the editor always count a tab as one
+    #[serde(default = "default_tab_width")]
+    pub tab_width: u32,
+    /// Label of the diagnostics, shown by editors next to the message
+    #[serde(default = "default_diagnostic_source")]
+    pub diagnostic_source: String,
+    #[serde(default)]
+    pub suggestion_engine: SuggestionEngine,
+    /// Accepted words in addition to the dictionary files
+    #[serde(default)]
+    pub words: Vec<String>,
+    /// Words that are always reported, `bad->good` names the spelling to use
+    #[serde(default)]
+    pub flag_words: Vec<String>,
+    /// Misspellings reported per document, the rest are summarized
+    #[serde(default = "default_max_diagnostics_per_file")]
+    pub max_diagnostics_per_file: usize,
+    /// Larger documents, e.g. minified or generated files, aren't checked
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: usize,
+    /// Characters that are part of words in addition to letters
+    #[serde(default)]
+    pub extra_word_chars: String,
+    /// Identifiers are checked as a whole before being split into their
+    /// camelCase parts, for dictionaries with compounding rules
+    #[serde(default)]
+    pub prefer_whole_word_check: bool,
+    /// Longer tokens are taken for generated strings and not checked
+    #[serde(default = "default_max_word_length")]
+    pub max_word_length: usize,
+    /// Skips strings that look generated, like base64 blobs and hashes
+    #[serde(default)]
+    pub skip_random_strings: bool,
+    /// Page explaining a diagnostic, the word is passed as `word` query parameter
+    #[serde(default)]
+    pub explanation_url: Option<String>,
+    /// Replaces the default English dictionary, dictionaries configured
+    /// by the user are kept as they are
+    #[serde(default)]
+    pub language_variant: LanguageVariant,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             dict_path: default_dict_path(),
+            workspace_dict_path: default_workspace_dict_path(),
             dictionaries: default_dictionaries(),
+            dictionary_groups: HashMap::new(),
+            default_group: None,
+            language_groups: HashMap::new(),
             diagnostic_severity: default_diagnostic_severity(),
+            diagnostic_presentation: default_diagnostic_presentation(),
+            ignore_markdown_links: default_ignore_markdown_links(),
+            suggestion_tiebreak: default_suggestion_tiebreak(),
+            proper_noun_suggestions: default_proper_noun_suggestions(),
+            max_suggestions: default_max_suggestions(),
+            eager_suggestions: false,
+            suggestion_budget_ms: default_suggestion_budget_ms(),
+            frequency_list: None,
+            include_files: vec![],
+            ignore_files: vec![],
+            ignore_regex: vec![],
+            respect_gitignore: default_respect_gitignore(),
+            add_all_immediately: false,
+            check_mode: default_check_mode(),
+            suppress_while_typing: false,
+            debounce_ms: default_debounce_ms(),
+            group_by_word: false,
+            check_scope: default_check_scope(),
+            operation_timeout_ms: default_operation_timeout_ms(),
+            download_timeout_ms: default_download_timeout_ms(),
+            checker_threads: default_checker_threads(),
+            ignore_ordinals: default_ignore_ordinals(),
+            min_word_length: default_min_word_length(),
+            check_short_acronyms: false,
+            prose_annotations: default_prose_annotations(),
+            skip_format_placeholders: default_skip_format_placeholders(),
+            clear_on_close: default_clear_on_close(),
+            tab_width: default_tab_width(),
+            diagnostic_source: default_diagnostic_source(),
+            suggestion_engine: SuggestionEngine::default(),
+            words: vec![],
+            flag_words: vec![],
+            max_diagnostics_per_file: default_max_diagnostics_per_file(),
+            max_file_bytes: default_max_file_bytes(),
+            extra_word_chars: String::new(),
+            prefer_whole_word_check: false,
+            max_word_length: default_max_word_length(),
+            skip_random_strings: false,
+            explanation_url: None,
+            language_variant: LanguageVariant::default(),
         }
     }
 }
 
+impl Config {
+    /// The lowercase `flag_words` with their preferred spelling, if any
+    pub fn flagged_words(&self) -> HashMap<String, Option<String>> {
+        self.flag_words
+            .iter()
+            .map(|entry| match entry.split_once("->") {
+                Some((bad, good)) => (bad.trim().to_lowercase(), Some(good.trim().to_string())),
+                None => (entry.trim().to_lowercase(), None),
+            })
+            .collect()
+    }
+
+    /// Swaps the default English dictionary for the one of `language_variant`
+    pub fn apply_language_variant(&mut self) {
+        let Some(variant) = self.language_variant.dictionary() else {
+            return;
+        };
+        let default_en = default_dictionaries()
+            .into_iter()
+            .find(|d| d.language == "en");
+        for dictionary in &mut self.dictionaries {
+            if Some(&*dictionary) == default_en.as_ref() {
+                *dictionary = variant.clone();
+            }
+        }
+    }
+
+    /// Dictionaries of the group selected for `language_id`,
+    /// `None` means every dictionary is consulted.
+    pub fn active_dictionaries(&self, language_id: &str) -> Option<Vec<String>> {
+        let group = self
+            .language_groups
+            .get(language_id)
+            .or(self.default_group.as_ref())?;
+        match self.dictionary_groups.get(group) {
+            Some(dictionaries) => Some(dictionaries.clone()),
+            None => {
+                warn!("Unknown dictionary group \"{group}\", using all dictionaries");
+                None
+            }
+        }
+    }
+}
+
+/// Names of the project config file in the workspace root, the first one
+/// found is used
+pub const PROJECT_CONFIG_FILES: [&str; 3] =
+    [".rustproof.toml", "rustproof.toml", ".rustproof.json"];
+
+/// Names of a CSpell config in the workspace root, see `cspell_layer`
+pub const CSPELL_CONFIG_FILES: [&str; 2] = ["cspell.json", ".cspell.json"];
+
+/// The word lists of a CSpell config, other keys are ignored
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct CSpellConfig {
+    #[serde(default)]
+    words: Vec<String>,
+    #[serde(default)]
+    flag_words: Vec<String>,
+    #[serde(default)]
+    ignore_words: Vec<String>,
+}
+
+/// Turns the word lists of a `cspell.json` into a config layer.
+/// CSpell's `ignoreWords` are accepted like its `words`.
+pub fn cspell_layer(content: &str) -> serde_json::Result<Value> {
+    let cspell: CSpellConfig = serde_json::from_str(content)?;
+    let words = cspell
+        .words
+        .into_iter()
+        .chain(cspell.ignore_words)
+        .collect::<Vec<_>>();
+    Ok(serde_json::json!({ "words": words, "flag_words": cspell.flag_words }))
+}
+
+/// Layers the project config over the user config. Lists are appended so a
+/// project can add `ignore_regex` patterns or words without dropping the
+/// user's, tables like `dictionary_groups` are merged key by key and every
+/// other value, e.g. `diagnostic_severity`, is replaced.
+pub fn merge_layers(user: Value, project: Value) -> Value {
+    match (user, project) {
+        (Value::Array(mut user), Value::Array(project)) => {
+            for value in project {
+                if !user.contains(&value) {
+                    user.push(value);
+                }
+            }
+            Value::Array(user)
+        }
+        (Value::Object(mut user), Value::Object(project)) => {
+            for (key, value) in project {
+                let merged = match user.remove(&key) {
+                    Some(previous) => merge_layers(previous, value),
+                    None => value,
+                };
+                user.insert(key, merged);
+            }
+            Value::Object(user)
+        }
+        (_, project) => project,
+    }
+}
+
+fn default_max_diagnostics_per_file() -> usize {
+    1000
+}
+
+fn default_max_file_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_word_length() -> usize {
+    40
+}
+
 fn default_diagnostic_severity() -> ConfigDiagnosticSeverity {
     ConfigDiagnosticSeverity::Error
 }
 
+fn default_diagnostic_presentation() -> DiagnosticPresentation {
+    DiagnosticPresentation::Underline
+}
+
+fn default_ignore_markdown_links() -> bool {
+    true
+}
+
+fn default_suggestion_tiebreak() -> SuggestionTiebreak {
+    SuggestionTiebreak::Frequency
+}
+
+fn default_max_suggestions() -> usize {
+    6
+}
+
+fn default_suggestion_budget_ms() -> u64 {
+    200
+}
+
+fn default_proper_noun_suggestions() -> ProperNounSuggestions {
+    ProperNounSuggestions::Keep
+}
+
+fn default_check_mode() -> CheckMode {
+    CheckMode::File
+}
+
+fn default_check_scope() -> CheckScope {
+    CheckScope::All
+}
+
+fn default_debounce_ms() -> u64 {
+    150
+}
+
+fn default_download_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_checker_threads() -> usize {
+    1
+}
+
+fn default_operation_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_ignore_ordinals() -> bool {
+    true
+}
+
+fn default_respect_gitignore() -> bool {
+    true
+}
+
+fn default_min_word_length() -> usize {
+    4
+}
+
+fn default_tab_width() -> u32 {
+    1
+}
+
+fn default_diagnostic_source() -> String {
+    "rustproof".to_string()
+}
+
+fn default_clear_on_close() -> bool {
+    true
+}
+
+fn default_skip_format_placeholders() -> bool {
+    true
+}
+
+fn default_prose_annotations() -> Vec<String> {
+    ["doc", "deprecated", "must_use", "error", "description"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Hunspell dictionaries of many languages, see https://github.com/wooorm/dictionaries
+const WOOORM_BASE_URL: &str =
+    "https://raw.githubusercontent.com/wooorm/dictionaries/main/dictionaries";
+
 fn default_dictionaries() -> Vec<Dictionary> {
     let base_url =
         "https://raw.githubusercontent.com/redsuperbat/rustproof/refs/heads/main/dictionaries";
@@ -110,16 +721,79 @@ fn default_dictionaries() -> Vec<Dictionary> {
         Dictionary::new(
             "en-code",
             &(base_url.to_string() + "/en-code/index.aff"),
-            &(base_url.to_string() + "/en-code/index.aff"),
+            &(base_url.to_string() + "/en-code/index.dic"),
         ),
         Dictionary::new(
             "en",
             &(base_url.to_string() + "/en/index.aff"),
-            &(base_url.to_string() + "/en/index.aff"),
+            &(base_url.to_string() + "/en/index.dic"),
         ),
     ]
 }
 
+/// Hunspell .dic files start with the number of words, unlike .aff files
+/// or error pages served in their place. Only that line is read, the words
+/// may be in another encoding than UTF-8, e.g. ISO8859-1.
+async fn is_dic_file(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path).await else {
+        return false;
+    };
+    let mut first_line = vec![];
+    if BufReader::new(file)
+        .read_until(b'\n', &mut first_line)
+        .await
+        .is_err()
+    {
+        return false;
+    }
+    let first_line = first_line
+        .strip_prefix("\u{feff}".as_bytes())
+        .unwrap_or(&first_line[..]);
+    std::str::from_utf8(first_line).is_ok_and(|l| l.trim().parse::<usize>().is_ok())
+}
+
+/// The path of a dictionary file given as an absolute path, a path starting
+/// with `~` or a `file://` url, `None` for anything to download
+fn local_path(location: &str) -> Option<PathBuf> {
+    let path = if location.starts_with("file://") {
+        Url::parse(location).ok()?.to_file_path().ok()?
+    } else if location.starts_with('~') || Path::new(location).is_absolute() {
+        expand_tilde(location)?
+    } else {
+        return None;
+    };
+    Some(std::fs::canonicalize(&path).unwrap_or(path))
+}
+
+/// Removes everything in `source_dir` except the `version` directory
+async fn remove_stale_versions(source_dir: &Path, version: &str) {
+    let Ok(mut entries) = fs::read_dir(source_dir).await else {
+        return;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.file_name().to_str() == Some(version) {
+            continue;
+        }
+        let path = entry.path();
+        info!("Removing stale dictionary {:?}", path);
+        let removed = if path.is_dir() {
+            fs::remove_dir_all(&path).await
+        } else {
+            fs::remove_file(&path).await
+        };
+        if let Err(e) = removed {
+            warn!("Unable to remove stale dictionary {:?}: {e}", path);
+        }
+    }
+}
+
+// Stable across rust versions unlike DefaultHasher, used for cache paths
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 async fn ensure_directory(path: &PathBuf) {
     if path.exists() {
         return;
@@ -136,6 +810,10 @@ fn default_dict_path() -> PathBuf {
     path
 }
 
+fn default_workspace_dict_path() -> PathBuf {
+    PathBuf::from(".rustproof/words.txt")
+}
+
 pub fn expand_tilde<P: AsRef<Path>>(path_user_input: P) -> Option<PathBuf> {
     let p = path_user_input.as_ref();
     if !p.starts_with("~") {
@@ -155,3 +833,303 @@ pub fn expand_tilde<P: AsRef<Path>>(path_user_input: P) -> Option<PathBuf> {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn it_versions_the_cache_by_url_unless_pinned() {
+        let a = Dictionary::new("en", "https://a.com/index.aff", "https://a.com/index.dic");
+        let b = Dictionary::new("en", "https://b.com/index.aff", "https://b.com/index.dic");
+        let base_dir = Path::new("/cache");
+        assert_ne!(a.cache_dir(base_dir), b.cache_dir(base_dir));
+        assert_eq!(a.cache_dir(base_dir), a.clone().cache_dir(base_dir));
+
+        let pinned = Dictionary {
+            version: Some("2024-01".to_string()),
+            ..a
+        };
+        assert_eq!(pinned.cache_version(), "2024-01");
+    }
+
+    #[tokio::test]
+    async fn failed_downloads_leave_no_file_behind() {
+        let path = std::env::temp_dir().join("rustproof-failed-download.dic");
+        let _ = std::fs::remove_file(&path);
+
+        // Nothing listens on the discard port
+        let download = Download {
+            url: "http://127.0.0.1:9/index.dic",
+            sha256: None,
+            timeout: Duration::from_millis(100),
+        };
+        let downloaded = download_if_not_exists(&path, &download).await;
+
+        assert!(downloaded.is_err());
+        assert!(!path.exists());
+        assert!(!path.with_extension("part").exists());
+    }
+
+    #[test]
+    fn it_verifies_checksums_when_given() {
+        let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(verify_sha256(b"hello", None), Ok(()));
+        assert_eq!(verify_sha256(b"hello", Some(hello)), Ok(()));
+        assert_eq!(verify_sha256(b"hello", Some(&hello.to_uppercase())), Ok(()));
+        assert!(verify_sha256(b"hell", Some(hello)).is_err());
+    }
+
+    #[tokio::test]
+    async fn corrupt_cached_files_are_downloaded_again() {
+        let path = std::env::temp_dir().join("rustproof-corrupt-cache.dic");
+        std::fs::write(&path, "truncated").unwrap();
+
+        let download = Download {
+            url: "http://127.0.0.1:9/index.dic",
+            sha256: Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"),
+            timeout: Duration::from_millis(100),
+        };
+        // The download fails but the corrupt file is gone
+        assert!(download_if_not_exists(&path, &download).await.is_err());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn cached_files_without_a_checksum_are_used_as_is() {
+        let path = std::env::temp_dir().join("rustproof-unchecked-cache.dic");
+        std::fs::write(&path, "1\nhello\n").unwrap();
+
+        let download = Download {
+            url: "http://127.0.0.1:9/index.dic",
+            sha256: None,
+            timeout: Duration::from_millis(100),
+        };
+        assert_eq!(download_if_not_exists(&path, &download).await, Ok(()));
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_detects_files_that_are_not_dic_files() {
+        let dir = std::env::temp_dir().join("rustproof-dic-check");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.dic"), "\u{feff}2\nhello\nworld\n").unwrap();
+        std::fs::write(dir.join("index.aff"), "SET UTF-8\nTRY esiano\n").unwrap();
+        std::fs::write(dir.join("latin1.dic"), b"2\nh\xe9llo\nw\xf6rld\n").unwrap();
+
+        assert!(is_dic_file(&dir.join("index.dic")).await);
+        assert!(is_dic_file(&dir.join("latin1.dic")).await);
+        assert!(!is_dic_file(&dir.join("index.aff")).await);
+        assert!(!is_dic_file(&dir.join("missing.dic")).await);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn default_dictionaries_fetch_both_files() {
+        for dict in default_dictionaries() {
+            assert!(dict.aff.ends_with("/index.aff"), "{}", dict.aff);
+            assert!(dict.dic.ends_with("/index.dic"), "{}", dict.dic);
+        }
+    }
+
+    #[test]
+    fn it_swaps_the_english_dictionary_for_the_language_variant() {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "language_variant": "en-GB"
+        }))
+        .unwrap();
+        config.apply_language_variant();
+        let languages = config
+            .dictionaries
+            .iter()
+            .map(|d| (d.language.as_str(), d.dic.rsplit('/').nth(1).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(languages, vec![("en-code", "en-code"), ("en", "en-GB")]);
+
+        let own = Dictionary::new("en", "/dicts/en.aff", "/dicts/en.dic");
+        config.dictionaries = vec![own.clone()];
+        config.apply_language_variant();
+        assert!(config.dictionaries == vec![own]);
+
+        let mut config = Config::default();
+        config.apply_language_variant();
+        assert!(config.dictionaries == default_dictionaries());
+    }
+
+    #[tokio::test]
+    async fn it_uses_local_dictionary_files_in_place() {
+        let dir = std::env::temp_dir().join("rustproof-local-dictionary");
+        std::fs::create_dir_all(&dir).unwrap();
+        let aff = dir.join("en_US.aff");
+        let dic = dir.join("en_US.dic");
+        std::fs::write(&aff, "SET UTF-8\n").unwrap();
+        std::fs::write(&dic, "1\nhello\n").unwrap();
+        let dic_url = Url::from_file_path(&dic).unwrap();
+
+        let dict = Dictionary::new("en", aff.to_str().unwrap(), dic_url.as_str());
+        let paths = dict.resolve(Duration::from_secs(1)).await.unwrap();
+
+        assert_eq!(paths.aff, std::fs::canonicalize(&aff).unwrap());
+        assert_eq!(paths.dic, std::fs::canonicalize(&dic).unwrap());
+        assert_eq!(
+            local_path("~/dictionaries/en.aff"),
+            dirs::home_dir().map(|h| h.join("dictionaries/en.aff"))
+        );
+        assert_eq!(local_path("https://example.com/en/index.aff"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_uses_a_fresh_cache_dir_when_the_version_changes() {
+        let base_dir = std::env::temp_dir().join("rustproof-dictionary-versions");
+        let _ = std::fs::remove_dir_all(&base_dir);
+        let mut dict = Dictionary::new("en", "https://a.com/index.aff", "https://a.com/index.dic");
+        dict.version = Some("v1".to_string());
+        let old_dir = dict.cache_dir(&base_dir);
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::write(old_dir.join("index.dic"), "cached").unwrap();
+
+        dict.version = Some("v2".to_string());
+        let new_dir = dict.cache_dir(&base_dir);
+        assert_ne!(old_dir, new_dir);
+        // Nothing cached for the new version, so it gets downloaded
+        assert!(!new_dir.join("index.dic").exists());
+
+        std::fs::create_dir_all(&new_dir).unwrap();
+        remove_stale_versions(&dict.source_dir(&base_dir), &dict.cache_version()).await;
+        assert!(!old_dir.exists());
+        assert!(new_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_cache_of_other_dictionaries_of_a_language() {
+        let base_dir = std::env::temp_dir().join("rustproof-dictionary-sources");
+        let _ = std::fs::remove_dir_all(&base_dir);
+        let en = Dictionary::new("en", "https://a.com/en.aff", "https://a.com/en.dic");
+        let medical = Dictionary::new("en", "https://b.com/med.aff", "https://b.com/med.dic");
+        let en_dir = en.cache_dir(&base_dir);
+        let medical_dir = medical.cache_dir(&base_dir);
+        assert_ne!(en_dir, medical_dir);
+        std::fs::create_dir_all(&en_dir).unwrap();
+        std::fs::create_dir_all(&medical_dir).unwrap();
+
+        remove_stale_versions(&en.source_dir(&base_dir), &en.cache_version()).await;
+        remove_stale_versions(&medical.source_dir(&base_dir), &medical.cache_version()).await;
+
+        assert!(en_dir.exists());
+        assert!(medical_dir.exists());
+        std::fs::remove_dir_all(&base_dir).unwrap();
+    }
+
+    #[test]
+    fn it_selects_the_dictionary_group_of_a_language() {
+        let config = Config {
+            dictionary_groups: HashMap::from([
+                (
+                    "web".to_string(),
+                    vec!["en".to_string(), "html".to_string()],
+                ),
+                ("prose".to_string(), vec!["en".to_string()]),
+            ]),
+            default_group: Some("prose".to_string()),
+            language_groups: HashMap::from([
+                ("html".to_string(), "web".to_string()),
+                ("rust".to_string(), "missing".to_string()),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.active_dictionaries("html"),
+            Some(vec!["en".to_string(), "html".to_string()])
+        );
+        assert_eq!(
+            config.active_dictionaries("markdown"),
+            Some(vec!["en".to_string()])
+        );
+        assert_eq!(config.active_dictionaries("rust"), None);
+        assert_eq!(Config::default().active_dictionaries("html"), None);
+    }
+
+    #[test]
+    fn it_parses_flag_words_with_a_preferred_spelling() {
+        let config = Config {
+            flag_words: vec!["Masterfile".to_string(), "teh -> the".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            config.flagged_words(),
+            HashMap::from([
+                ("masterfile".to_string(), None),
+                ("teh".to_string(), Some("the".to_string())),
+            ])
+        );
+    }
+
+    #[test]
+    fn it_reads_the_word_lists_of_a_cspell_config() {
+        let layer = cspell_layer(
+            r#"{
+                "version": "0.2",
+                "words": ["kubernetes"],
+                "ignoreWords": ["wrold"],
+                "flagWords": ["hte->the"],
+                "ignorePaths": ["target"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            layer,
+            json!({ "words": ["kubernetes", "wrold"], "flag_words": ["hte->the"] })
+        );
+    }
+
+    #[test]
+    fn project_lists_are_appended_to_user_lists() {
+        let merged = merge_layers(
+            json!({ "ignore_regex": ["[0-9a-f]{40}"] }),
+            json!({ "ignore_regex": ["x[0-9]+", "[0-9a-f]{40}"] }),
+        );
+        assert_eq!(
+            merged,
+            json!({ "ignore_regex": ["[0-9a-f]{40}", "x[0-9]+"] })
+        );
+    }
+
+    #[test]
+    fn project_scalars_replace_user_scalars() {
+        let merged = merge_layers(
+            json!({ "diagnostic_severity": "error", "min_word_length": 3 }),
+            json!({ "diagnostic_severity": "hint" }),
+        );
+        assert_eq!(
+            merged,
+            json!({ "diagnostic_severity": "hint", "min_word_length": 3 })
+        );
+    }
+
+    #[test]
+    fn project_tables_are_merged_by_key() {
+        let merged = merge_layers(
+            json!({ "dictionary_groups": { "web": ["en"], "prose": ["en"] } }),
+            json!({ "dictionary_groups": { "web": ["html"] }, "default_group": "web" }),
+        );
+        assert_eq!(
+            merged,
+            json!({
+                "dictionary_groups": { "web": ["en", "html"], "prose": ["en"] },
+                "default_group": "web"
+            })
+        );
+    }
+
+    #[test]
+    fn merged_layers_fall_back_to_defaults() {
+        let merged = merge_layers(json!({}), json!({ "check_mode": "word" }));
+        let config: Config = serde_json::from_value(merged).unwrap();
+        assert_eq!(config.check_mode, CheckMode::Word);
+        assert_eq!(config.min_word_length, default_min_word_length());
+    }
+}
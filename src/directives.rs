@@ -0,0 +1,74 @@
+use crate::lexer::Token;
+use crop::Rope;
+use std::collections::HashSet;
+
+const DISABLE_FILE: &str = "rustproof-disable-file";
+const DISABLE_LINE: &str = "rustproof-disable-line";
+
+/// `rustproof-disable-file` and `rustproof-disable-line` directives, usually
+/// written in comments. They are found in the raw lines since the lexer
+/// drops the punctuation they are made of.
+#[derive(Default)]
+pub struct Directives {
+    disable_file: bool,
+    disabled_lines: HashSet<u32>,
+}
+
+impl Directives {
+    pub fn new(rope: &Rope) -> Self {
+        let mut directives = Directives::default();
+        for (i, line) in rope.lines().enumerate() {
+            let line = line.to_string();
+            if line.contains(DISABLE_FILE) {
+                directives.disable_file = true;
+            }
+            if line.contains(DISABLE_LINE) {
+                directives.disabled_lines.insert(i as u32);
+            }
+        }
+        directives
+    }
+
+    /// Whether `line` holds a directive
+    pub fn in_line(line: &str) -> bool {
+        line.contains(DISABLE_FILE) || line.contains(DISABLE_LINE)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.disable_file && self.disabled_lines.is_empty()
+    }
+
+    pub fn is_disabled(&self, token: &Token) -> bool {
+        self.disable_file || self.disabled_lines.contains(&token.start.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::rope_tokens;
+    use crate::position::PositionEncoding;
+
+    fn checked_words(str: &str) -> String {
+        let rope = Rope::from(str);
+        let directives = Directives::new(&rope);
+        rope_tokens(&rope, false, PositionEncoding::Utf16, &[])
+            .filter(|t| !directives.is_disabled(t))
+            .map(|t| t.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn it_disables_lines() {
+        let str = "let helo = 1; // rustproof-disable-line\nlet wrold = 2;";
+        assert_eq!(checked_words(str), "let wrold");
+    }
+
+    #[test]
+    fn it_disables_files() {
+        let str = "# rustproof-disable-file\nhelo wrold";
+        assert_eq!(checked_words(str), "");
+        assert!(Directives::new(&Rope::from("helo wrold")).is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::lexer::{Lexer, Pos, Token};
+use crate::position::{byte_offset_for_position, PositionEncoding};
+use crop::Rope;
+use tower_lsp::lsp_types::{Diagnostic, TextDocumentContentChangeEvent};
+
+/// Applies a change sent by the client, changes without a range replace the
+/// whole document.
+pub fn apply_content_change(
+    source: &mut Rope,
+    change: &TextDocumentContentChangeEvent,
+    encoding: PositionEncoding,
+) {
+    let Some(range) = change.range else {
+        *source = Rope::from(change.text.as_str());
+        return;
+    };
+    let start = byte_offset_for_position(source, &range.start.into(), encoding);
+    let end = byte_offset_for_position(source, &range.end.into(), encoding);
+    source.replace(start..end, &change.text);
+}
+
+/// Where the cursor ends up after typing `change`, i.e. the end of the
+/// inserted text. `None` for changes that replace the whole document.
+pub fn cursor_after(
+    change: &TextDocumentContentChangeEvent,
+    encoding: PositionEncoding,
+) -> Option<Pos> {
+    let start = change.range?.start;
+    Some(match change.text.rsplit_once('\n') {
+        Some((before, last_line)) => Pos {
+            line: start.line + before.matches('\n').count() as u32 + 1,
+            col: encoding.str_len(last_line),
+        },
+        None => Pos {
+            line: start.line,
+            col: start.character + encoding.str_len(&change.text),
+        },
+    })
+}
+
+/// A change confined to a single line, columns are in the negotiated unit
+#[derive(Debug, PartialEq)]
+pub struct LineEdit {
+    pub line: u32,
+    pub start_col: u32,
+    /// End of the replaced text in the old line
+    pub old_end_col: u32,
+    /// End of the inserted text in the new line
+    pub new_end_col: u32,
+}
+
+impl LineEdit {
+    /// The edit made by `change` if it stays within a single line.
+    /// Returns `None` for changes that replace the whole document or span lines.
+    pub fn from_change(
+        change: &TextDocumentContentChangeEvent,
+        encoding: PositionEncoding,
+    ) -> Option<LineEdit> {
+        let range = change.range?;
+        if range.start.line != range.end.line || change.text.contains(['\n', '\r']) {
+            return None;
+        }
+        Some(LineEdit {
+            line: range.start.line,
+            start_col: range.start.character,
+            old_end_col: range.end.character,
+            new_end_col: range.start.character + encoding.str_len(&change.text),
+        })
+    }
+
+    /// Tokens of the new line which touch the edited text
+    pub fn edited_tokens(
+        &self,
+        new: &Rope,
+        encoding: PositionEncoding,
+        skip_ordinals: bool,
+        extra_word_chars: &[char],
+    ) -> Vec<Token> {
+        let line = new.line(self.line as usize);
+        if !line.chars().any(|c| c.is_alphabetic()) {
+            return vec![];
+        }
+        let line_start = new.byte_of_line(self.line as usize);
+        let pos = Pos {
+            line: self.line,
+            col: 0,
+        };
+        // Lexing stops at the end of the line instead of the document
+        Lexer::from_rope_at(new, line_start, pos)
+            .skip_ordinals(skip_ordinals)
+            .encoding(encoding)
+            .extra_word_chars(extra_word_chars)
+            .take_while(|t| t.start.line == self.line)
+            .filter(|t| t.start.col <= self.new_end_col && self.start_col <= t.end.col)
+            .collect()
+    }
+
+    /// Drops the `previous` diagnostics touched by the edit, shifts the ones
+    /// after it on the same line and adds the diagnostics of the edited tokens.
+    pub fn merge_diagnostics(
+        &self,
+        previous: &[Diagnostic],
+        edited_tokens: &[Token],
+        edited_diagnostics: Vec<Diagnostic>,
+    ) -> Vec<Diagnostic> {
+        let span_start = edited_tokens
+            .iter()
+            .map(|t| t.start.col)
+            .fold(self.start_col, u32::min);
+        let span_end = edited_tokens
+            .iter()
+            .map(|t| t.end.col)
+            .fold(self.new_end_col, u32::max);
+        let delta = self.new_end_col as i64 - self.old_end_col as i64;
+
+        let mut diagnostics = previous
+            .iter()
+            .filter_map(|d| {
+                let range = d.range;
+                if range.start.line != self.line || range.end.character <= span_start {
+                    return Some(d.clone());
+                }
+                if range.start.character < self.old_end_col {
+                    return None;
+                }
+                let mut d = d.clone();
+                d.range.start.character = (range.start.character as i64 + delta) as u32;
+                d.range.end.character = (range.end.character as i64 + delta) as u32;
+                (d.range.start.character >= span_end).then_some(d)
+            })
+            .chain(edited_diagnostics)
+            .collect::<Vec<_>>();
+        diagnostics.sort_by_key(|d| (d.range.start.line, d.range.start.character));
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    const UTF16: PositionEncoding = PositionEncoding::Utf16;
+
+    fn diagnostic(line: u32, start: u32, end: u32) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            ..Default::default()
+        }
+    }
+
+    fn change(range: Option<Range>, text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn it_applies_incremental_changes() {
+        let mut source = Rope::from("🤖 helo\nwrold");
+        let insert = Range::new(Position::new(0, 5), Position::new(0, 5));
+        apply_content_change(&mut source, &change(Some(insert), "l"), UTF16);
+        let replace = Range::new(Position::new(1, 0), Position::new(1, 5));
+        apply_content_change(&mut source, &change(Some(replace), "world"), UTF16);
+        assert_eq!(source.to_string(), "🤖 hello\nworld");
+
+        apply_content_change(&mut source, &change(None, "new text"), UTF16);
+        assert_eq!(source.to_string(), "new text");
+    }
+
+    #[test]
+    fn it_finds_the_cursor_after_a_change() {
+        let at = Range::new(Position::new(1, 3), Position::new(1, 3));
+        assert_eq!(
+            cursor_after(&change(Some(at), "lé"), UTF16),
+            Some(Pos { line: 1, col: 5 })
+        );
+        assert_eq!(
+            cursor_after(&change(Some(at), "a\nb\n🤖"), UTF16),
+            Some(Pos { line: 3, col: 2 })
+        );
+        assert_eq!(cursor_after(&change(None, "text"), UTF16), None);
+    }
+
+    fn edit_of(line: u32, start: u32, end: u32, text: &str) -> Option<LineEdit> {
+        let range = Range::new(Position::new(line, start), Position::new(line, end));
+        LineEdit::from_change(&change(Some(range), text), UTF16)
+    }
+
+    #[test]
+    fn it_finds_a_single_line_edit() {
+        assert_eq!(
+            edit_of(0, 7, 7, "l"),
+            Some(LineEdit {
+                line: 0,
+                start_col: 7,
+                old_end_col: 7,
+                new_end_col: 8,
+            })
+        );
+        assert_eq!(
+            edit_of(1, 2, 4, "🤖"),
+            Some(LineEdit {
+                line: 1,
+                start_col: 2,
+                old_end_col: 4,
+                new_end_col: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn it_rejects_multi_line_edits() {
+        let across = Range::new(Position::new(0, 3), Position::new(1, 0));
+        assert_eq!(
+            LineEdit::from_change(&change(Some(across), ""), UTF16),
+            None
+        );
+        assert_eq!(edit_of(0, 3, 3, "\nthree"), None);
+        assert_eq!(
+            LineEdit::from_change(&change(None, "uno\ndos"), UTF16),
+            None
+        );
+    }
+
+    #[test]
+    fn it_finds_the_edited_tokens() {
+        let new = Rope::from("say hello wrold");
+        let edit = edit_of(0, 7, 7, "l").unwrap();
+        let tokens = edit.edited_tokens(&new, UTF16, false, &[]);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].lexeme, "hello");
+    }
+
+    #[test]
+    fn it_skips_ordinal_suffixes_of_edited_tokens() {
+        let new = Rope::from("the 21st line");
+        let edit = edit_of(0, 6, 6, "s").unwrap();
+        assert!(edit.edited_tokens(&new, UTF16, true, &[]).is_empty());
+        assert_eq!(edit.edited_tokens(&new, UTF16, false, &[])[0].lexeme, "st");
+    }
+
+    #[test]
+    fn it_only_replaces_diagnostics_of_the_edited_word() {
+        let new = Rope::from("hello wrold\nlnie");
+        let edit = edit_of(0, 3, 3, "l").unwrap();
+        let previous = vec![
+            diagnostic(0, 0, 4),
+            diagnostic(0, 5, 10),
+            diagnostic(1, 0, 4),
+        ];
+
+        let merged = edit.merge_diagnostics(
+            &previous,
+            &edit.edited_tokens(&new, UTF16, false, &[]),
+            vec![],
+        );
+
+        assert_eq!(merged, vec![diagnostic(0, 6, 11), diagnostic(1, 0, 4)]);
+    }
+}
@@ -1,7 +1,7 @@
-use crate::{buffered_peekable::BufferedPeekable, lexer::Token};
+use crate::{buffered_peekable::BufferedPeekable, lexer::Token, position::PositionEncoding};
 
 pub trait Expandable {
-    fn expand(&self) -> Vec<Token>;
+    fn expand(&self, encoding: PositionEncoding) -> Vec<Token>;
 }
 
 pub struct Expander<I: Iterator<Item = char>> {
@@ -24,14 +24,18 @@ impl<I: Iterator<Item = char>> Expander<I> {
     }
 
     fn next_word(&mut self) -> Option<String> {
-        let (Some(c1), Some(c2)) = (self.text.next(), self.text.peek()) else {
-            return None;
-        };
-        match (c1.is_uppercase(), c2.is_uppercase()) {
-            (true, false) => self.parse_pascal(c1),
+        let c1 = self.text.next()?;
+        // A digit run like the `256` in `sha256Hash` is a word of its own
+        if c1.is_ascii_digit() {
+            return self.parse_digits(c1);
+        }
+        let c2_is_upper = self.text.peek().is_some_and(|c| c.is_uppercase());
+        match (c1.is_uppercase(), c2_is_upper) {
+            // An uppercase run like `HTTP` in `HTTPServer` stays together
             (true, true) => self.parse_upper(c1),
-            (false, false) => self.parse_lower(c1),
-            _ => None,
+            (true, false) => self.parse_pascal(c1),
+            // Also handles single characters before an uppercase one, e.g. `iPhone`
+            (false, _) => self.parse_lower(c1),
         }
     }
 
@@ -47,6 +51,18 @@ impl<I: Iterator<Item = char>> Expander<I> {
         Some(word)
     }
 
+    fn parse_digits(&mut self, first: char) -> Option<String> {
+        let mut word = String::from(first);
+        while let Some(next) = self.text.peek() {
+            if next.is_ascii_digit() {
+                word.push(self.text.next().unwrap());
+            } else {
+                break;
+            }
+        }
+        Some(word)
+    }
+
     fn parse_upper(&mut self, first: char) -> Option<String> {
         let mut word = String::from(first);
         loop {
@@ -81,23 +97,30 @@ impl<I: Iterator<Item = char>> Expander<I> {
 }
 
 impl Expandable for Token {
-    fn expand(&self) -> Vec<Token> {
+    fn expand(&self, encoding: PositionEncoding) -> Vec<Token> {
         let only_alphanumeric = self.lexeme.chars().all(|c| c.is_alphanumeric());
         if !only_alphanumeric {
             return vec![self.to_owned()];
         }
         let mut start = self.start.col;
+        let mut start_byte = self.start_byte;
         Expander::new(self.lexeme.chars())
             .into_iter()
-            .map(|lexeme| {
-                let lexeme_len = lexeme.len() as u32;
+            .filter_map(|lexeme| {
+                // Columns are in the same unit as in the lexer
+                let lexeme_len = encoding.str_len(&lexeme);
                 let out_token = Token {
                     start: self.start.set_col(start),
                     end: self.end.set_col(start + lexeme_len),
+                    start_byte,
+                    end_byte: start_byte + lexeme.len(),
                     lexeme,
                 };
                 start += lexeme_len;
-                out_token
+                start_byte = out_token.end_byte;
+                // Numbers aren't spelled
+                let is_number = out_token.lexeme.chars().all(|c| c.is_ascii_digit());
+                (!is_number).then_some(out_token)
             })
             .collect()
     }
@@ -106,6 +129,7 @@ impl Expandable for Token {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::lexer::Pos;
 
     fn expand(str: &str) -> Vec<String> {
         Expander::new(str.chars()).collect()
@@ -118,4 +142,126 @@ mod test {
         assert_eq!(expand("DataJSON"), vec!["Data", "JSON"]);
         assert_eq!(expand("DataJSONGood"), vec!["Data", "JSON", "Good"]);
     }
+
+    #[test]
+    fn it_keeps_uppercase_runs_together() {
+        assert_eq!(expand("HTTPServer"), vec!["HTTP", "Server"]);
+        assert_eq!(
+            expand("parseHTTPResponse"),
+            vec!["parse", "HTTP", "Response"]
+        );
+        assert_eq!(expand("MAX"), vec!["MAX"]);
+    }
+
+    #[test]
+    fn it_expands_two_letter_casings() {
+        assert_eq!(expand("Ab"), vec!["Ab"]);
+        assert_eq!(expand("AB"), vec!["AB"]);
+        assert_eq!(expand("aB"), vec!["a", "B"]);
+    }
+
+    #[test]
+    fn it_keeps_words_after_single_characters() {
+        assert_eq!(expand("iPhone"), vec!["i", "Phone"]);
+        assert_eq!(expand("utf8Decoder"), vec!["utf", "8", "Decoder"]);
+        assert_eq!(expand("sha256Hash"), vec!["sha", "256", "Hash"]);
+        assert_eq!(expand("fooB"), vec!["foo", "B"]);
+    }
+
+    #[test]
+    fn it_uses_utf16_columns_for_expanded_tokens() {
+        let token = Token {
+            start: Pos { line: 0, col: 0 },
+            end: Pos { line: 0, col: 13 },
+            start_byte: 0,
+            end_byte: 16,
+            lexeme: "ÅngströmKlass".to_string(),
+        };
+        let cols = token
+            .expand(PositionEncoding::Utf16)
+            .into_iter()
+            .map(|t| (t.start.col, t.end.col))
+            .collect::<Vec<_>>();
+        assert_eq!(cols, vec![(0, 8), (8, 13)]);
+    }
+
+    #[test]
+    fn it_keeps_columns_of_accented_words_in_code_units() {
+        let token = Token {
+            start: Pos { line: 0, col: 2 },
+            end: Pos { line: 0, col: 8 },
+            start_byte: 2,
+            end_byte: 10,
+            lexeme: "FööBar".to_string(),
+        };
+        let expanded = token
+            .expand(PositionEncoding::Utf16)
+            .into_iter()
+            .map(|t| (t.lexeme, t.start.col, t.end.col, t.start_byte, t.end_byte))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            expanded,
+            vec![
+                ("Föö".to_string(), 2, 5, 2, 7),
+                ("Bar".to_string(), 5, 8, 7, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_expands_camel_case_tokens() {
+        let token = Token {
+            start: Pos { line: 2, col: 4 },
+            end: Pos { line: 2, col: 18 },
+            start_byte: 4,
+            end_byte: 18,
+            lexeme: "fizzBuzzHelper".to_string(),
+        };
+        let expanded = token
+            .expand(PositionEncoding::Utf16)
+            .into_iter()
+            .map(|t| (t.lexeme, t.start.col, t.end.col))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            expanded,
+            vec![
+                ("fizz".to_string(), 4, 8),
+                ("Buzz".to_string(), 8, 12),
+                ("Helper".to_string(), 12, 18)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_splits_on_digits_and_drops_numbers() {
+        let expand_token = |lexeme: &str| {
+            let token = Token {
+                start: Pos { line: 0, col: 0 },
+                end: Pos {
+                    line: 0,
+                    col: lexeme.len() as u32,
+                },
+                start_byte: 0,
+                end_byte: lexeme.len(),
+                lexeme: lexeme.to_string(),
+            };
+            token
+                .expand(PositionEncoding::Utf16)
+                .into_iter()
+                .map(|t| (t.lexeme, t.start.col, t.end.col))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            expand_token("utf8Encoder"),
+            vec![("utf".to_string(), 0, 3), ("Encoder".to_string(), 4, 11)]
+        );
+        assert_eq!(
+            expand_token("base64Decode"),
+            vec![("base".to_string(), 0, 4), ("Decode".to_string(), 6, 12)]
+        );
+        assert_eq!(
+            expand_token("md5sum"),
+            vec![("md".to_string(), 0, 2), ("sum".to_string(), 3, 6)]
+        );
+    }
 }
@@ -0,0 +1,80 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Decides which documents are spell-checked based on the
+/// `include_files` and `ignore_files` globs
+pub struct FileFilter {
+    include: GlobSet,
+    ignore: GlobSet,
+}
+
+impl FileFilter {
+    pub fn new(include: &[String], ignore: &[String]) -> Result<Self, globset::Error> {
+        Ok(Self {
+            include: build_glob_set(include)?,
+            ignore: build_glob_set(ignore)?,
+        })
+    }
+
+    /// Ignore globs take precedence, when there are no include globs
+    /// every file that isn't ignored is checked
+    pub fn is_checked(&self, path: &Path) -> bool {
+        if self.ignore.is_match(path) {
+            return false;
+        }
+        self.include.is_empty() || self.include.is_match(path)
+    }
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self {
+            include: GlobSet::empty(),
+            ignore: GlobSet::empty(),
+        }
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], ignore: &[&str]) -> FileFilter {
+        let to_vec = |p: &[&str]| p.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        FileFilter::new(&to_vec(include), &to_vec(ignore)).unwrap()
+    }
+
+    #[test]
+    fn it_checks_everything_by_default() {
+        let filter = FileFilter::default();
+        assert!(filter.is_checked(Path::new("src/main.rs")));
+    }
+
+    #[test]
+    fn it_skips_files_not_matching_includes() {
+        let filter = filter(&["docs/**/*.md"], &[]);
+        assert!(filter.is_checked(Path::new("docs/guide/intro.md")));
+        assert!(!filter.is_checked(Path::new("src/main.rs")));
+    }
+
+    #[test]
+    fn it_lets_ignores_win_over_includes() {
+        let filter = filter(&["docs/**/*.md"], &["docs/generated/**"]);
+        assert!(filter.is_checked(Path::new("docs/guide/intro.md")));
+        assert!(!filter.is_checked(Path::new("docs/generated/api.md")));
+    }
+
+    #[test]
+    fn it_rejects_invalid_globs() {
+        let patterns = vec!["docs/[".to_string()];
+        assert!(FileFilter::new(&patterns, &[]).is_err());
+    }
+}
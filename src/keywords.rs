@@ -0,0 +1,95 @@
+const RUST: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "usize", "isize", "bool", "str",
+];
+
+const JAVASCRIPT: &[&str] = &[
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "null",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+    "readonly",
+    "keyof",
+    "namespace",
+    "interface",
+    "implements",
+    "declare",
+];
+
+const RUBY: &[&str] = &[
+    "alias", "and", "begin", "break", "case", "class", "def", "defined", "do", "else", "elsif",
+    "end", "ensure", "false", "for", "if", "in", "module", "next", "nil", "not", "or", "redo",
+    "rescue", "retry", "return", "self", "super", "then", "true", "undef", "unless", "until",
+    "when", "while", "yield",
+];
+
+/// Keywords of the language with the LSP languageId `language_id`,
+/// they are part of the syntax so they are never reported.
+pub fn from_lang(language_id: &str) -> &'static [&'static str] {
+    match language_id {
+        "rust" => RUST,
+        "javascript" | "javascriptreact" | "typescript" | "typescriptreact" => JAVASCRIPT,
+        "ruby" => RUBY,
+        _ => &[],
+    }
+}
+
+/// Keywords are compared case-insensitively so `True` matches `true`
+pub fn is_keyword(language_id: &str, word: &str) -> bool {
+    from_lang(language_id)
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_keywords_of_the_language() {
+        assert!(is_keyword("rust", "impl"));
+        assert!(is_keyword("ruby", "Elsif"));
+        assert!(is_keyword("typescript", "instanceof"));
+        assert!(!is_keyword("rust", "elsif"));
+        assert!(!is_keyword("plaintext", "impl"));
+    }
+}
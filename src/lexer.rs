@@ -1,4 +1,8 @@
+use crate::position::PositionEncoding;
+use crop::iter::Chars;
+use crop::Rope;
 use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Pos {
@@ -51,6 +55,12 @@ impl Location {
             end: Pos::start(),
         }
     }
+
+    /// Whether `pos` lies within this location, end exclusive
+    pub fn contains(&self, pos: &Pos) -> bool {
+        let pos = (pos.line, pos.col);
+        (self.start.line, self.start.col) <= pos && pos < (self.end.line, self.end.col)
+    }
 }
 
 #[derive(Debug)]
@@ -59,6 +69,10 @@ pub struct Lexer<I: Iterator<Item = char>> {
     col: u32,
     line: u32,
     offset: usize,
+    prev: Option<char>,
+    skip_ordinals: bool,
+    encoding: PositionEncoding,
+    extra_word_chars: Vec<char>,
 }
 
 impl<I: Iterator<Item = char>> Iterator for Lexer<I> {
@@ -73,9 +87,53 @@ impl<I: Iterator<Item = char>> Iterator for Lexer<I> {
 pub struct Token {
     pub start: Pos,
     pub end: Pos,
+    /// Byte offsets of the token in the lexed text, unlike the columns
+    /// they are independent of the negotiated position encoding
+    pub start_byte: usize,
+    pub end_byte: usize,
     pub lexeme: String,
 }
 
+impl Token {
+    /// Moves a token lexed from a single line to `line`, which starts at
+    /// byte `line_start`
+    pub fn on_line(self, line: u32, line_start: usize) -> Token {
+        Token {
+            start: Pos {
+                line,
+                col: self.start.col,
+            },
+            end: Pos {
+                line,
+                col: self.end.col,
+            },
+            start_byte: line_start + self.start_byte,
+            end_byte: line_start + self.end_byte,
+            lexeme: self.lexeme,
+        }
+    }
+}
+
+/// Lexes `rope` line by line. Tokens never span lines, so lines without a
+/// single letter, common in symbol heavy code, are skipped without lexing.
+pub fn rope_tokens<'a>(
+    rope: &'a Rope,
+    skip_ordinals: bool,
+    encoding: PositionEncoding,
+    extra_word_chars: &'a [char],
+) -> impl Iterator<Item = Token> + 'a {
+    rope.lines()
+        .enumerate()
+        .filter(|(_, line)| line.chars().any(|c| c.is_alphabetic()))
+        .flat_map(move |(i, line)| {
+            Lexer::new(line.chars())
+                .skip_ordinals(skip_ordinals)
+                .encoding(encoding)
+                .extra_word_chars(extra_word_chars)
+                .map(move |t| t.on_line(i as u32, rope.byte_of_line(i)))
+        })
+}
+
 impl Into<Location> for &Token {
     fn into(self) -> Location {
         Location::new(self.start, self.end)
@@ -88,6 +146,24 @@ impl Into<Location> for Token {
     }
 }
 
+impl<'a> Lexer<Chars<'a>> {
+    /// Lexes `rope` from `byte_offset` on, which must be at a char boundary
+    /// and at `pos`, without walking the text before it
+    pub fn from_rope_at(rope: &'a Rope, byte_offset: usize, pos: Pos) -> Self {
+        Self {
+            text: rope.byte_slice(byte_offset..).chars(),
+            col: pos.col,
+            line: pos.line,
+            offset: byte_offset,
+            // Needed to recognize ordinal suffixes right at the offset
+            prev: rope.byte_slice(..byte_offset).chars().next_back(),
+            skip_ordinals: false,
+            encoding: PositionEncoding::default(),
+            extra_word_chars: vec![],
+        }
+    }
+}
+
 impl<I: Iterator<Item = char>> Lexer<I> {
     pub fn new(text: I) -> Self {
         Self {
@@ -95,9 +171,39 @@ impl<I: Iterator<Item = char>> Lexer<I> {
             col: 0,
             line: 0,
             offset: 0,
+            prev: None,
+            skip_ordinals: false,
+            encoding: PositionEncoding::default(),
+            extra_word_chars: vec![],
         }
     }
 
+    /// Drops the suffix of ordinals such as `21st` and `3rd`
+    /// which would otherwise become the tokens `st` and `rd`
+    pub fn skip_ordinals(mut self, skip: bool) -> Self {
+        self.skip_ordinals = skip;
+        self
+    }
+
+    /// Counts columns in `encoding` units, UTF-16 unless told otherwise
+    pub fn encoding(mut self, encoding: PositionEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Characters accepted in words in addition to letters, e.g. `_` to keep
+    /// `snake_case` identifiers in one piece
+    pub fn extra_word_chars(mut self, chars: &[char]) -> Self {
+        self.extra_word_chars = chars.to_vec();
+        self
+    }
+
+    fn is_ordinal_suffix(&self, lexeme: &str, after_digit: bool) -> bool {
+        self.skip_ordinals
+            && after_digit
+            && matches!(lexeme.to_lowercase().as_str(), "st" | "nd" | "rd" | "th")
+    }
+
     fn pos(&self) -> Pos {
         Pos {
             line: self.line,
@@ -105,47 +211,35 @@ impl<I: Iterator<Item = char>> Lexer<I> {
         }
     }
 
+    /// Combining marks belong to the letter before them, e.g. the accent
+    /// of a decomposed `e\u{301}`
     fn is_accepted_char(&self, char: char) -> bool {
-        match char {
-            'a'..='z'
-            | 'A'..='Z'
-            | 'å'
-            | 'Å'
-            | 'ä'
-            | 'Ä'
-            | 'ö'
-            | 'Ö'
-            | 'ø'
-            | 'í'
-            | 'ü'
-            | 'ą'
-            | 'ô'
-            | 'č'
-            | 'ę'
-            | 'ė'
-            | 'į'
-            | 'š'
-            | 'ų'
-            | 'ž' => true,
-            _ => false,
-        }
+        char.is_alphabetic() || is_combining_mark(char) || self.extra_word_chars.contains(&char)
     }
 
     fn next_token(&mut self) -> Option<Token> {
         let start = self.pos();
+        let start_byte = self.offset;
+        let after_digit = self.prev.is_some_and(|c| c.is_ascii_digit());
         let mut lexeme = String::new();
         let mut maybe_quote: Option<char> = None;
 
-        let mut end;
+        // The end of the last accepted char, a trailing quote isn't part of the token
+        let mut end = start;
+        let mut end_byte = start_byte;
         loop {
-            end = self.pos();
-
             let Some(char) = self.next() else {
                 // We are at the end of the file
-                if lexeme.is_empty() {
+                if lexeme.is_empty() || self.is_ordinal_suffix(&lexeme, after_digit) {
                     return None;
                 }
-                return Some(Token { lexeme, start, end });
+                return Some(Token {
+                    lexeme,
+                    start,
+                    end,
+                    start_byte,
+                    end_byte,
+                });
             };
 
             match char {
@@ -157,6 +251,8 @@ impl<I: Iterator<Item = char>> Lexer<I> {
                         maybe_quote = None
                     }
                     lexeme += &char.to_string();
+                    end = self.pos();
+                    end_byte = self.offset;
                 }
                 '\'' => {
                     if lexeme.is_empty() {
@@ -168,11 +264,17 @@ impl<I: Iterator<Item = char>> Lexer<I> {
             }
         }
 
-        if lexeme.is_empty() {
+        if lexeme.is_empty() || self.is_ordinal_suffix(&lexeme, after_digit) {
             return self.next_token();
         }
 
-        Some(Token { end, start, lexeme })
+        Some(Token {
+            end,
+            start,
+            start_byte,
+            end_byte,
+            lexeme,
+        })
     }
 
     fn next(&mut self) -> Option<char> {
@@ -182,13 +284,13 @@ impl<I: Iterator<Item = char>> Lexer<I> {
             self.col = 0;
             self.line += 1;
         } else {
-            // Use UTF-16 code units for LSP compatibility
-            // BMP characters (U+0000 to U+FFFF) = 1 code unit
-            // Characters outside BMP (emoji, etc.) = 2 code units (surrogate pair)
-            self.col += char.len_utf16() as u32;
+            // Columns are in the unit negotiated with the client, for UTF-16
+            // characters outside the BMP (emoji, etc.) take 2 code units
+            self.col += self.encoding.char_len(char);
         }
 
-        self.offset += 1;
+        self.offset += char.len_utf8();
+        self.prev = Some(char);
 
         Some(char)
     }
@@ -227,6 +329,12 @@ mod tests {
         assert_eq!(token.start.col, 3);
     }
 
+    #[test]
+    fn it_keeps_letters_of_any_script_in_words() {
+        let str = "καλημέρα, привет_мир İstanbul'da ığdır";
+        assert_eq!(tokenize(str), "καλημέρα привет мир İstanbul'da ığdır");
+    }
+
     #[test]
     fn it_creates_tokens_with_single_quotes() {
         let str = "fn fizz_buzz(n: string){ return \"what's up dude\"}";
@@ -242,6 +350,54 @@ mod tests {
         assert_eq!(tokens, "fn fizz buzz n string return hello n");
     }
 
+    #[test]
+    fn it_skips_ordinal_suffixes() {
+        let str = "the 21st and 3rd time, 2nd floor 100TH";
+        let tokens = Lexer::new(str.chars())
+            .skip_ordinals(true)
+            .map(|v| v.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(tokens, "the and time floor");
+        assert_eq!(tokenize(str), "the st and rd time nd floor TH");
+    }
+
+    #[test]
+    fn it_keeps_ordinal_like_words_not_after_digits() {
+        let str = "1 st nd1st";
+        let tokens = Lexer::new(str.chars())
+            .skip_ordinals(true)
+            .map(|v| v.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(tokens, "st nd");
+    }
+
+    #[test]
+    fn it_ends_the_last_token_at_the_end_of_the_file() {
+        let tokens = Lexer::new("wordd".chars()).collect::<Vec<_>>();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].start, Pos { line: 0, col: 0 });
+        assert_eq!(tokens[0].end, Pos { line: 0, col: 5 });
+    }
+
+    #[test]
+    fn it_drops_a_trailing_quote() {
+        let tokens = Lexer::new("word'".chars()).collect::<Vec<_>>();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].lexeme, "word");
+        assert_eq!(tokens[0].end, Pos { line: 0, col: 4 });
+
+        let tokens = Lexer::new("dogs' toys".chars()).collect::<Vec<_>>();
+        assert_eq!(tokens[0].lexeme, "dogs");
+        assert_eq!(tokens[0].end, Pos { line: 0, col: 4 });
+    }
+
+    #[test]
+    fn it_yields_no_tokens_for_an_empty_file() {
+        assert_eq!(Lexer::new("".chars()).count(), 0);
+    }
+
     #[test]
     fn it_handles_tabs_as_single_utf16_unit() {
         // Tab is 1 UTF-16 code unit, not visual width
@@ -254,13 +410,128 @@ mod tests {
     #[test]
     fn it_handles_surrogate_pairs() {
         // Characters outside BMP use 2 UTF-16 code units (surrogate pair)
-        // 𐐀 (U+10400) is outside BMP
-        let str = "a𐐀b";
+        // 😀 (U+1F600) is outside BMP
+        let str = "a😀b";
         let tokens = Lexer::new(str.chars()).collect::<Vec<_>>();
-        // 'a' at col 0, '𐐀' at col 1 (2 code units), 'b' at col 3
+        // 'a' at col 0, '😀' at col 1 (2 code units), 'b' at col 3
         let token = tokens.get(0).unwrap(); // "a"
         assert_eq!(token.start.col, 0);
         let token = tokens.get(1).unwrap(); // "b"
         assert_eq!(token.start.col, 3); // 1 + 2 = 3
     }
+
+    #[test]
+    fn it_tracks_byte_offsets_of_tokens() {
+        let rope = Rope::from("🤖 café\nwrold");
+        let tokens = rope_tokens(&rope, false, PositionEncoding::Utf16, &[])
+            .map(|t| (t.start_byte, t.end_byte))
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, vec![(5, 10), (11, 16)]);
+
+        // The inherent `next` of the lexer yields chars, not tokens
+        let tokens = Lexer::from_rope_at(&rope, 11, Pos { line: 1, col: 0 }).collect::<Vec<_>>();
+        assert_eq!(
+            rope.byte_slice(tokens[0].start_byte..tokens[0].end_byte),
+            "wrold"
+        );
+    }
+
+    #[test]
+    fn it_accepts_extra_word_chars() {
+        let lex = |extra_word_chars: &[char]| {
+            Lexer::new("let snake_case = 1;".chars())
+                .extra_word_chars(extra_word_chars)
+                .map(|t| t.lexeme)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lex(&[]), vec!["let", "snake", "case"]);
+        assert_eq!(lex(&['_']), vec!["let", "snake_case"]);
+    }
+
+    #[test]
+    fn it_keeps_combining_marks_in_words() {
+        let tokens = Lexer::new("cafe\u{301} bar".chars())
+            .map(|t| (t.lexeme, t.start.col, t.end.col))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![("cafe\u{301}".to_string(), 0, 5), ("bar".to_string(), 6, 9)]
+        );
+    }
+
+    #[test]
+    fn it_counts_utf8_columns_in_bytes() {
+        let tokens = Lexer::new("a😀b café".chars())
+            .encoding(PositionEncoding::Utf8)
+            .map(|t| (t.lexeme, t.start.col, t.end.col))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                ("a".to_string(), 0, 1),
+                ("b".to_string(), 5, 6),
+                ("café".to_string(), 7, 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_lexes_ropes_from_an_offset() {
+        let str = "🤖 café wrold\nthe 21st and_more";
+        let rope = Rope::from(str);
+        let full = Lexer::new(str.chars())
+            .skip_ordinals(true)
+            .collect::<Vec<_>>();
+
+        for (byte_offset, pos) in [
+            (0, Pos { line: 0, col: 0 }),
+            ("🤖 café ".len(), Pos { line: 0, col: 8 }),
+            ("🤖 café wrold\nthe 21".len(), Pos { line: 1, col: 6 }),
+        ] {
+            let expected = full
+                .iter()
+                .filter(|t| (t.start.line, t.start.col) >= (pos.line, pos.col))
+                .map(|t| (t.lexeme.clone(), t.start, t.end))
+                .collect::<Vec<_>>();
+            let tokens = Lexer::from_rope_at(&rope, byte_offset, pos)
+                .skip_ordinals(true)
+                .map(|t| (t.lexeme, t.start, t.end))
+                .collect::<Vec<_>>();
+            assert_eq!(tokens, expected, "lexing from {pos:?}");
+        }
+    }
+
+    #[test]
+    fn it_lexes_ropes_line_by_line() {
+        let rope = Rope::from("fn main() {\n    [1, 2] == {};\n\thelo 21st\n}");
+        let tokens = rope_tokens(&rope, true, PositionEncoding::Utf16, &[])
+            .map(|t| (t.lexeme, t.start.line, t.start.col, t.end.col))
+            .collect::<Vec<_>>();
+        let lexed = Lexer::new(rope.chars())
+            .skip_ordinals(true)
+            .map(|t| (t.lexeme, t.start.line, t.start.col, t.end.col))
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, lexed);
+        assert_eq!(tokens[2], ("helo".to_string(), 2, 1, 5));
+    }
+
+    /// Run with `cargo test --release bench_symbol_dense_lines -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_symbol_dense_lines() {
+        let line = "    0x1f, 0x2e, 0x3d, 0x4c, [(1 + 2) * 3] => {} // ---- ==== ++++\n";
+        let text = format!("{}{}", line.repeat(50_000), "let wrold = 1;\n");
+        let rope = Rope::from(text.as_str());
+
+        let started = std::time::Instant::now();
+        let lexed = Lexer::new(rope.chars()).count();
+        let lexer_time = started.elapsed();
+
+        let started = std::time::Instant::now();
+        let fast = rope_tokens(&rope, false, PositionEncoding::Utf16, &[]).count();
+        let fast_path_time = started.elapsed();
+
+        println!("lexer: {lexer_time:?}, line fast path: {fast_path_time:?}");
+        assert_eq!(lexed, fast);
+    }
 }
@@ -1,7 +1,46 @@
+use crate::suggestions::edit_distance;
 use dashmap::DashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
 
 pub struct LocalDictionary(DashSet<String>);
 
+/// Everything the user taught rustproof, used to move it between machines
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DictionaryState {
+    /// Words of the user dictionary
+    pub words: Vec<String>,
+    /// Words of the dictionary of each workspace folder
+    #[serde(default)]
+    pub workspace_words: BTreeMap<PathBuf, Vec<String>>,
+    /// Words ignored until the server restarts
+    #[serde(default)]
+    pub session_words: Vec<String>,
+}
+
+/// Words are compared lowercase and in their composed form, `e\u{301}` is `é`
+fn key(word: &str) -> String {
+    word.nfc().collect::<String>().to_lowercase()
+}
+
+/// The words of a dictionary file sorted, without empty lines and with only
+/// the first spelling of each word, e.g. of `Kubernetes` and `kubernetes`
+pub fn cleaned_lines(content: &str) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .filter(|l| seen.insert(key(l)))
+        .collect::<Vec<_>>();
+    lines.sort_by_cached_key(|l| key(l));
+    lines
+}
+
 // Local dictionary abstraction turns all words lowercase
 impl LocalDictionary {
     pub fn new() -> Self {
@@ -9,10 +48,113 @@ impl LocalDictionary {
     }
 
     pub fn contains(&self, v: &str) -> bool {
-        self.0.contains(&v.to_lowercase())
+        self.0.contains(&key(v))
     }
 
     pub fn insert(&self, v: String) {
-        self.0.insert(v.to_lowercase());
+        self.0.insert(key(&v));
+    }
+
+    /// Returns whether the word was in the dictionary
+    pub fn remove(&self, v: &str) -> bool {
+        self.0.remove(&key(v)).is_some()
+    }
+
+    pub fn clear(&self) {
+        self.0.clear();
+    }
+
+    /// Words within `max_distance` edits of `word`, closest first
+    pub fn near(&self, word: &str, max_distance: usize) -> Vec<String> {
+        let word = key(word);
+        let mut near = self
+            .0
+            .iter()
+            .map(|w| (edit_distance(&word, &w), w.to_string()))
+            .filter(|(distance, w)| *distance <= max_distance && *w != word)
+            .collect::<Vec<_>>();
+        near.sort();
+        near.into_iter().map(|(_, w)| w).collect()
+    }
+
+    /// All words in alphabetical order
+    pub fn words(&self) -> Vec<String> {
+        let mut words = self.0.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        words.sort();
+        words
+    }
+
+    /// Inserts every non-empty line of the file at `path`. Lines that are not
+    /// valid UTF-8 are skipped instead of failing the whole file, the number
+    /// of skipped lines is returned.
+    pub fn load_file(&self, path: &Path) -> io::Result<usize> {
+        let bytes = fs::read(path)?;
+        let mut skipped = 0;
+        for line in bytes.split(|b| *b == b'\n') {
+            match std::str::from_utf8(line) {
+                Ok(w) if w.trim().is_empty() => {}
+                Ok(w) => self.insert(w.trim().to_string()),
+                Err(_) => skipped += 1,
+            }
+        }
+        Ok(skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_treats_decomposed_letters_like_composed_ones() {
+        let dict = LocalDictionary::new();
+        dict.insert("Cafe\u{301}".to_string());
+        assert!(dict.contains("café"));
+        assert_eq!(dict.words(), vec!["café"]);
+    }
+
+    #[test]
+    fn it_skips_lines_with_invalid_utf8() {
+        let path = std::env::temp_dir().join("rustproof-invalid-utf8-dict.txt");
+        fs::write(&path, b"kubernetes\n\xff\xfebroken\nrustproof\n").unwrap();
+
+        let dict = LocalDictionary::new();
+        let skipped = dict.load_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(skipped, 1);
+        assert!(dict.contains("kubernetes"));
+        assert!(dict.contains("rustproof"));
+    }
+
+    #[test]
+    fn it_skips_empty_lines() {
+        let path = std::env::temp_dir().join("rustproof-empty-lines-dict.txt");
+        fs::write(&path, "kubernetes\r\n\n  \nrustproof\n").unwrap();
+
+        let dict = LocalDictionary::new();
+        dict.load_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(dict.words(), vec!["kubernetes", "rustproof"]);
+    }
+
+    #[test]
+    fn it_cleans_lines_keeping_their_casing() {
+        assert_eq!(
+            cleaned_lines("wrold\r\nKubernetes\n\n  \nkubernetes\nWrold\nhelm\n"),
+            vec!["helm", "Kubernetes", "wrold"]
+        );
+    }
+
+    #[test]
+    fn it_finds_words_near_a_misspelling() {
+        let dict = LocalDictionary::new();
+        dict.insert("kubernetes".to_string());
+        dict.insert("kubectl".to_string());
+        dict.insert("rustproof".to_string());
+
+        assert_eq!(dict.near("Kubernets", 2), vec!["kubernetes"]);
+        assert_eq!(dict.near("rustprof", 0), Vec::<String>::new());
     }
 }
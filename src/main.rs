@@ -1,99 +1,686 @@
+use cache::BoundedCache;
 use clap::{crate_version, Parser};
-use config::{expand_tilde, Config};
+use cli::OutputFormat;
+use config::{
+    cspell_layer, expand_tilde, merge_layers, CheckMode, CheckScope, Config,
+    DiagnosticPresentation, DictionaryPath, SuggestionEngine, CSPELL_CONFIG_FILES,
+    PROJECT_CONFIG_FILES,
+};
 use crop::Rope;
 use dashmap::DashMap;
+use directives::Directives;
+use edit::{apply_content_change, cursor_after, LineEdit};
 use expander::Expandable;
-use hunspell_rs::{CheckResult, Hunspell};
-use lexer::{Lexer, Token};
-use local_dictionary::LocalDictionary;
-use log::info;
-use parking_lot::RwLock;
+use file_filter::FileFilter;
+use lexer::{rope_tokens, Pos, Token};
+use local_dictionary::{cleaned_lines, DictionaryState, LocalDictionary};
+use log::{debug, error, info, warn};
+use markdown::LINK_TARGETS;
+use numbers::NUMERIC_LITERALS;
+use parking_lot::{Mutex, RwLock};
+use paths::FILE_PATHS;
+use position::{byte_offset_for_position, PositionEncoding};
+use progress::WorkDone;
+use random_strings::RANDOM_STRINGS;
+use regex::Regex;
+use scope::{Scope, Scopes, Syntax};
 use serde_json::Value;
-use std::collections::{HashMap, HashSet};
+use skipped_spans::SkippedSpans;
+use spellchecker::{new_spellchecker, SpellChecker};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::str::FromStr;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, OnceLock};
 use std::thread;
+use std::time::{Duration, Instant};
+use suggestions::{edit_distance, match_case, SuggestionRanker};
+use symspell::SymSpell;
+use tokio::task::JoinHandle;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
+use unicode_normalization::UnicodeNormalization;
+use urls::URLS;
 
 mod buffered_peekable;
+mod cache;
+mod cli;
 mod config;
+mod directives;
+mod edit;
 mod expander;
+mod file_filter;
+mod keywords;
 mod lexer;
 mod local_dictionary;
+mod markdown;
+mod numbers;
+mod paths;
+mod position;
+mod progress;
+mod random_strings;
+// Human-readable output for reports outside of the editor
+mod report;
+mod scope;
+mod segment;
+mod skipped_spans;
+mod spellchecker;
+mod suggestions;
+mod symspell;
+mod urls;
+mod workspace;
 
 type SourceCode = Rope;
+/// Words remembered by the check and suggestion caches
+const CACHE_CAPACITY: usize = 10_000;
+/// How far a typo may be from a local dictionary word for it to be suggested
+const MAX_LOCAL_SUGGESTION_DISTANCE: usize = 2;
+/// Other occurrences of a word linked from each of its diagnostics
+const MAX_RELATED_OCCURRENCES: usize = 20;
+/// Words looked up between two chances for a newer change to cancel a check
+const CHECK_CHUNK_WORDS: usize = 256;
+/// The code action fixing every misspelling of a document at once
+const FIX_ALL: CodeActionKind = CodeActionKind::new("source.fixAll.rustproof");
+/// Watchers of the user and workspace dictionary files
+const DICT_FILES_REGISTRATION: &str = "rustproof-dict-files";
+/// A word and the dictionaries to consult or all of them when `None`, answered
+/// with whether any of them knows the word and the ones that don't
+type CheckRequest = (
+    String,
+    Option<Vec<String>>,
+    oneshot::Sender<(bool, Vec<String>)>,
+);
+/// A checked word and the dictionaries it was checked against
+type CheckKey = (String, Option<Vec<String>>);
+/// A word answered with the suggestions of every dictionary, ranked
+type SuggestRequest = (String, oneshot::Sender<Vec<String>>);
+
+/// Served by the checker threads, which check words and suggest fixes with
+/// the same dictionary instances
+enum SpellRequest {
+    Check(CheckRequest),
+    Suggest(SuggestRequest),
+}
+
+/// The dictionaries, loaded once and shared by every checker thread
+struct Spellcheckers {
+    // Hunspell can't serve two threads at once, every dictionary has its
+    // own lock so threads checking against different ones don't wait
+    checkers: Vec<(String, Mutex<Box<dyn SpellChecker>>)>,
+    paths: Vec<DictionaryPath>,
+    suggestion_engine: SuggestionEngine,
+    ranker: SuggestionRanker,
+    // Built on the first request for suggestions
+    symspell: OnceLock<SymSpell>,
+}
+
+impl Spellcheckers {
+    fn answer(&self, request: SpellRequest) {
+        match request {
+            SpellRequest::Check((word, dictionaries, send)) => {
+                let (known_by, rejected_by): (Vec<_>, Vec<_>) = self
+                    .checkers
+                    .iter()
+                    .filter(|(language, _)| {
+                        dictionaries.as_ref().is_none_or(|d| d.contains(language))
+                    })
+                    .partition(|(_, c)| c.lock().check(&word));
+                let rejected_by = rejected_by.into_iter().map(|(l, _)| l.clone()).collect();
+                let _ = send.send((!known_by.is_empty(), rejected_by));
+            }
+            SpellRequest::Suggest((word, send)) => {
+                let suggestions = match self.suggestion_engine {
+                    SuggestionEngine::Hunspell => self
+                        .checkers
+                        .iter()
+                        .flat_map(|(_, c)| c.lock().suggest(&word))
+                        .collect(),
+                    SuggestionEngine::Symspell => self
+                        .symspell
+                        .get_or_init(|| SymSpell::from_dictionaries(&self.paths))
+                        .suggest(&word),
+                };
+                // Suggestions shorter than 2 characters are usually bad
+                let suggestions = suggestions
+                    .into_iter()
+                    .filter(|s| s.len() > 2)
+                    .collect::<Vec<_>>();
+                // Removes duplicates and puts the closest match first
+                let _ = send.send(self.ranker.rank(&word, suggestions));
+            }
+        }
+    }
+}
 
 struct Backend {
     version: &'static str,
     client: Client,
     config: RwLock<Config>,
+    file_filter: RwLock<FileFilter>,
+    // Compiled `ignore_regex` patterns
+    ignore_regexes: RwLock<Vec<Regex>>,
+    // See `Config::flagged_words`
+    flagged_words: RwLock<HashMap<String, Option<String>>>,
+    workspace_root: RwLock<Option<PathBuf>>,
+    // Every folder of a multi-root workspace, the first one is `workspace_root`
+    workspace_folders: RwLock<Vec<PathBuf>>,
+    // Whether the client pulls the diagnostics of open documents
+    pull_diagnostics: RwLock<bool>,
+    // Whether the client shows progress, e.g. of loading the dictionaries
+    work_done_progress: RwLock<bool>,
+    // The unit columns are counted in, negotiated with the client
+    position_encoding: RwLock<PositionEncoding>,
     local_dict: LocalDictionary,
+    // Words of each workspace folder, see `workspace_dict_path`
+    workspace_dicts: DashMap<PathBuf, LocalDictionary>,
+    // The `words` of the config, never written to a dictionary file
+    config_words: LocalDictionary,
+    // Words ignored until the server restarts
+    session_words: LocalDictionary,
     sources: DashMap<Url, SourceCode>,
-    checker: RwLock<Option<mpsc::Sender<(String, oneshot::Sender<bool>)>>>,
-    suggester: RwLock<Option<mpsc::Sender<(String, oneshot::Sender<Vec<String>>)>>>,
+    // The LSP languageId of each open document
+    languages: DashMap<Url, String>,
+    // Last diagnostics published for each document
+    diagnostics: DashMap<Url, Vec<Diagnostic>>,
+    // Where the user is typing in each document, see `suppress_while_typing`
+    typing_cursors: DashMap<Url, Pos>,
+    // Pending full checks after a change, see `debounce_ms`
+    debounce_timers: DashMap<Url, JoinHandle<()>>,
+    // Changes received for each open document, to drop outdated checks
+    change_counts: DashMap<Url, u64>,
+    // Whether each document had directives at its last full check
+    has_directives: DashMap<Url, bool>,
+    spellchecker: RwLock<Option<mpsc::Sender<SpellRequest>>>,
+    // Answers of the spellchecker, cleared when it is restarted
+    check_cache: BoundedCache<CheckKey, (bool, Vec<String>)>,
+    suggestion_cache: BoundedCache<String, Vec<String>>,
 }
 
 impl Backend {
-    fn misspelled_tokens(&self, code: &SourceCode) -> Vec<Token> {
-        Lexer::new(code.chars())
+    fn new(client: Client) -> Self {
+        Self {
+            version: crate_version!(),
+            client,
+            local_dict: LocalDictionary::new(),
+            config_words: LocalDictionary::new(),
+            session_words: LocalDictionary::new(),
+            workspace_dicts: DashMap::new(),
+            config: RwLock::new(Config::default()),
+            file_filter: RwLock::new(FileFilter::default()),
+            ignore_regexes: RwLock::new(vec![]),
+            flagged_words: RwLock::new(HashMap::new()),
+            workspace_root: RwLock::new(None),
+            workspace_folders: RwLock::new(vec![]),
+            pull_diagnostics: RwLock::new(false),
+            work_done_progress: RwLock::new(false),
+            position_encoding: RwLock::new(PositionEncoding::default()),
+            sources: DashMap::new(),
+            languages: DashMap::new(),
+            diagnostics: DashMap::new(),
+            typing_cursors: DashMap::new(),
+            debounce_timers: DashMap::new(),
+            change_counts: DashMap::new(),
+            has_directives: DashMap::new(),
+            spellchecker: RwLock::new(None),
+            check_cache: BoundedCache::new(CACHE_CAPACITY),
+            suggestion_cache: BoundedCache::new(CACHE_CAPACITY),
+        }
+    }
+
+    /// `folder` is the workspace folder of the document, its dictionary
+    /// is consulted next to the user dictionary
+    fn misspelled_tokens(
+        &self,
+        code: &SourceCode,
+        language_id: &str,
+        folder: Option<&Path>,
+    ) -> Vec<Token> {
+        self.misspellings(code, language_id, folder, None)
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect()
+    }
+
+    /// Misspelled tokens with the severity they are reported with. `edited`
+    /// are the tokens of a single line of `code` to check instead of all of
+    /// it, lexed with the settings of a full check.
+    fn misspellings(
+        &self,
+        code: &SourceCode,
+        language_id: &str,
+        folder: Option<&Path>,
+        edited: Option<&[Token]>,
+    ) -> Vec<(Token, DiagnosticSeverity)> {
+        let candidates = self.candidates(code, language_id, folder, edited);
+        self.check_candidates(candidates, language_id)
+    }
+
+    /// Tokens of `code` left to look up in the dictionaries, i.e. those
+    /// that aren't skipped or known to a local dictionary
+    fn candidates(
+        &self,
+        code: &SourceCode,
+        language_id: &str,
+        folder: Option<&Path>,
+        edited: Option<&[Token]>,
+    ) -> Vec<Token> {
+        let (
+            ignore_markdown_links,
+            check_scope,
+            ignore_ordinals,
+            prose_annotations,
+            skip_format_placeholders,
+            check_short_acronyms,
+            min_word_length,
+            extra_word_chars,
+            prefer_whole_word_check,
+            max_word_length,
+            skip_random_strings,
+        ) = {
+            let config = self.config.read();
+            (
+                config.ignore_markdown_links,
+                config.check_scope,
+                config.ignore_ordinals,
+                config.prose_annotations.clone(),
+                config.skip_format_placeholders,
+                config.check_short_acronyms,
+                config.min_word_length,
+                config.extra_word_chars.chars().collect::<Vec<_>>(),
+                config.prefer_whole_word_check,
+                config.max_word_length,
+                config.skip_random_strings,
+            )
+        };
+        let dictionaries = { self.config.read().active_dictionaries(language_id) };
+        let encoding = *self.position_encoding.read();
+        // The edited words of word mode are lexed from their line alone, none
+        // of the skipped spans reach past it
+        let (text, text_start, start) = match edited {
+            Some(edited) => {
+                let Some(line) = edited.first().map(|t| t.start.line) else {
+                    return vec![];
+                };
+                let start = Pos { line, col: 0 };
+                let line = line as usize;
+                (code.line(line), code.byte_of_line(line), start)
+            }
+            None => (code.byte_slice(..), 0, Pos::start()),
+        };
+        // Word mode only checks documents without directives
+        let directives = match edited {
+            Some(_) => Directives::default(),
+            None => Directives::new(code),
+        };
+        let ignore_regexes = { self.ignore_regexes.read().clone() };
+        // The identifiers `ignore_regex` patterns are matched against are cut from it
+        let regex_text = if ignore_regexes.is_empty() {
+            String::new()
+        } else {
+            text.to_string()
+        };
+        // Letters of literals like `0xDEADBEEF` or `42px`, parts of paths like
+        // `src/main.rs`, urls and email addresses
+        let mut skipped_kinds = vec![&NUMERIC_LITERALS, &FILE_PATHS, &URLS];
+        // Link and image targets are urls and paths, not prose
+        if ignore_markdown_links {
+            skipped_kinds.push(&LINK_TARGETS);
+        }
+        // Generated tokens and blobs aren't words, however they are spelled
+        if skip_random_strings {
+            skipped_kinds.push(&RANDOM_STRINGS);
+        }
+        let skipped_spans =
+            SkippedSpans::starting_at(text.chars(), start, encoding, &skipped_kinds);
+        let scopes = match (check_scope, Syntax::for_language(language_id)) {
+            (CheckScope::CommentsStrings, Some(syntax)) => Some(Scopes::new(
+                syntax,
+                code.chars(),
+                &prose_annotations,
+                skip_format_placeholders,
+                encoding,
+            )),
+            _ => None,
+        };
+        // We ignore tokens with a lexeme shorter than `min_word_length`
+        // Those are not relevant for spelling mistakes, except for
+        // acronyms like `DB` when they are opted into
+        let long_enough = |t: &Token| {
+            t.lexeme.len() >= min_word_length || (check_short_acronyms && is_acronym(&t.lexeme))
+        };
+        // Flagged words are reported even if a dictionary knows them
+        let flagged_words = { self.flagged_words.read().clone() };
+        let is_flagged = |t: &Token| flagged_words.contains_key(&t.lexeme.to_lowercase());
+        let tokens = match edited {
+            Some(edited) => edited.to_vec(),
+            None => rope_tokens(code, ignore_ordinals, encoding, &extra_word_chars).collect(),
+        };
+        let tokens = tokens
+            .into_iter()
+            .filter(long_enough)
+            // Lines and files disabled with `rustproof-disable-*` comments
+            .filter(|t| !directives.is_disabled(t))
+            // Matched against the whole identifier so patterns can target
+            // digits and underscores too
+            .filter(|t| {
+                !ignore_regexes
+                    .iter()
+                    .any(|r| r.is_match(identifier_of(&regex_text, text_start, t)))
+            })
+            .filter(|t| {
+                scopes
+                    .as_ref()
+                    .is_none_or(|s| s.scope_of(&t.start) != Scope::Code)
+            })
+            .filter(|t| !skipped_spans.contains(t))
+            // Words this long are usually hashes or minified code
+            .filter(|t| t.lexeme.chars().count() <= max_word_length)
+            .collect::<Vec<_>>();
+        // Compounds the dictionaries know as a whole aren't split up
+        let known_whole_words = if prefer_whole_word_check {
+            self.known_compounds(&tokens, encoding, dictionaries.as_deref(), folder)
+        } else {
+            HashSet::new()
+        };
+        tokens
             .into_iter()
-            // We ignore tokens with a lexeme shorter than 4 characters
-            // Those are not relevant for spelling mistakes
-            .filter(|t| t.lexeme.len() > 3)
+            .filter(|t| !known_whole_words.contains(&t.lexeme))
             // Expand camelCase, PascalCase and ABBRCase etc.
-            .flat_map(|t| t.expand())
+            .flat_map(|t| t.expand(encoding))
+            // Decomposed letters are checked in their composed form, the
+            // columns still count the characters of the document
+            .map(|t| Token {
+                lexeme: t.lexeme.nfc().collect(),
+                ..t
+            })
             // After expansion the tokens could be broken into smaller ones
             // therefore we filter again the first is just a performance optimization
-            .filter(|t| t.lexeme.len() > 3)
-            // Hunspell spell-check
-            .filter(|t| !self.spell_check(&t.lexeme))
-            // Check against our local dictionary
-            .filter(|t| !self.local_dict.contains(&t.lexeme))
+            .filter(long_enough)
+            // Keywords like `impl` or `elsif` are syntax, not words
+            .filter(|t| !keywords::is_keyword(language_id, &t.lexeme))
+            // Check against our local dictionary first, it's an in-memory
+            // lookup so it saves a round-trip to hunspell
+            .filter(|t| is_flagged(t) || !self.in_local_dicts(&t.lexeme, folder))
+            .collect()
+    }
+
+    /// The misspelled `tokens` with the severity they are reported with
+    fn check_candidates(
+        &self,
+        tokens: Vec<Token>,
+        language_id: &str,
+    ) -> Vec<(Token, DiagnosticSeverity)> {
+        let (dictionaries, severity, dictionary_severities) = {
+            let config = self.config.read();
+            let dictionary_severities = config
+                .dictionaries
+                .iter()
+                .filter_map(|d| {
+                    Some((d.language.clone(), d.severity.as_ref()?.to_lsp_diagnostic()))
+                })
+                .collect::<HashMap<_, _>>();
+            (
+                config.active_dictionaries(language_id),
+                config.diagnostic_severity.to_lsp_diagnostic(),
+                dictionary_severities,
+            )
+        };
+        let flagged_words = { self.flagged_words.read().clone() };
+        let is_flagged = |t: &Token| flagged_words.contains_key(&t.lexeme.to_lowercase());
+        // Hunspell spell-check
+        let mut checked = self.check_words(
+            tokens.iter().map(|t| t.lexeme.as_str()),
+            dictionaries.as_deref(),
+        );
+        // Dictionaries often lack contractions and possessives, unknown ones
+        // get a second chance in another form
+        let variants = tokens
+            .iter()
+            .zip(&checked)
+            .enumerate()
+            .filter(|(_, (_, (known, _)))| !known)
+            .filter_map(|(i, (t, _))| Some((i, contraction_variant(&t.lexeme)?)))
+            .collect::<Vec<_>>();
+        let checked_variants = self.check_words(
+            variants.iter().map(|(_, w)| w.as_str()),
+            dictionaries.as_deref(),
+        );
+        for ((i, _), answer) in variants.iter().zip(checked_variants) {
+            if answer.0 {
+                checked[*i] = answer;
+            }
+        }
+        tokens
+            .into_iter()
+            .zip(checked)
+            .filter_map(|(t, (known, rejected_by))| {
+                if is_flagged(&t) {
+                    return Some((t, severity));
+                }
+                // The first rejecting dictionary with its own severity decides,
+                // e.g. hints for a code dictionary and warnings for prose. Known
+                // words are only reported by such dictionaries.
+                match rejected_by
+                    .iter()
+                    .find_map(|d| dictionary_severities.get(d).copied())
+                {
+                    Some(severity) => Some((t, severity)),
+                    None => (!known).then_some((t, severity)),
+                }
+            })
+            .collect()
+    }
+
+    /// Lexemes of the `tokens` that expansion would split but a dictionary
+    /// knows as a whole, e.g. compounds built by Hunspell affix rules
+    fn known_compounds(
+        &self,
+        tokens: &[Token],
+        encoding: PositionEncoding,
+        dictionaries: Option<&[String]>,
+        folder: Option<&Path>,
+    ) -> HashSet<String> {
+        let compounds = tokens
+            .iter()
+            .filter(|t| t.expand(encoding).len() > 1)
+            .collect::<Vec<_>>();
+        let lexemes = compounds
+            .iter()
+            .map(|t| t.lexeme.nfc().collect::<String>())
+            .collect::<Vec<_>>();
+        let checked = self.check_words(lexemes.iter().map(String::as_str), dictionaries);
+        compounds
+            .into_iter()
+            .zip(lexemes.iter().zip(checked))
+            .filter(|(_, (lexeme, (known, _)))| *known || self.in_local_dicts(lexeme, folder))
+            .map(|(t, _)| t.lexeme.to_string())
             .collect()
     }
 
-    fn spell_check_code(&self, code: &SourceCode) -> Vec<Diagnostic> {
-        let severity = { self.config.read().diagnostic_severity.clone() };
-        self.misspelled_tokens(code)
+    fn spell_check_code(
+        &self,
+        code: &SourceCode,
+        language_id: &str,
+        folder: Option<&Path>,
+    ) -> Vec<Diagnostic> {
+        self.spell_check_tokens(code, language_id, folder, None)
+    }
+
+    /// Diagnostics of the `edited` tokens of `code`, all of them when `None`
+    fn spell_check_tokens(
+        &self,
+        code: &SourceCode,
+        language_id: &str,
+        folder: Option<&Path>,
+        edited: Option<&[Token]>,
+    ) -> Vec<Diagnostic> {
+        let misspellings = self.misspellings(code, language_id, folder, edited);
+        self.diagnostics_of(&misspellings, folder)
+    }
+
+    /// Diagnostics of the `misspellings`, up to `max_diagnostics_per_file`
+    fn diagnostics_of(
+        &self,
+        misspellings: &[(Token, DiagnosticSeverity)],
+        folder: Option<&Path>,
+    ) -> Vec<Diagnostic> {
+        let (
+            presentation,
+            eager_suggestions,
+            suggestion_budget,
+            source,
+            max_diagnostics,
+            explanation_url,
+        ) = {
+            let config = self.config.read();
+            (
+                config.diagnostic_presentation,
+                config.eager_suggestions,
+                Duration::from_millis(config.suggestion_budget_ms),
+                config.diagnostic_source.clone(),
+                config.max_diagnostics_per_file,
+                config
+                    .explanation_url
+                    .as_deref()
+                    .and_then(|url| Url::parse(url).ok()),
+            )
+        };
+        let code_description = |word: &str| {
+            let mut href = explanation_url.clone()?;
+            href.query_pairs_mut().append_pair("word", word);
+            Some(CodeDescription { href })
+        };
+        let started = Instant::now();
+        // Suggestions are computed until the budget runs out, the remaining
+        // words still get suggestions through code actions
+        let message = |word: &str| {
+            if let Some(preferred) = self.flagged_words.read().get(&word.to_lowercase()) {
+                return match preferred {
+                    Some(preferred) => format!("Flagged word \"{word}\", use \"{preferred}\""),
+                    None => format!("Flagged word \"{word}\""),
+                };
+            }
+            let within_budget = eager_suggestions && started.elapsed() < suggestion_budget;
+            let suggestion = within_budget
+                .then(|| self.suggest(word, folder))
+                .and_then(|s| s.into_iter().next());
+            match suggestion {
+                Some(suggestion) => {
+                    format!("Unknown word \"{word}\", did you mean \"{suggestion}\"?")
+                }
+                None => format!("Unknown word \"{word}\""),
+            }
+        };
+        let mut diagnostics = misspellings
             .iter()
-            .map(|t| Diagnostic {
+            .take(max_diagnostics)
+            .map(|(t, severity)| {
+                let (severity, tags) = match presentation {
+                    DiagnosticPresentation::Underline => (*severity, None),
+                    DiagnosticPresentation::Subtle => (
+                        DiagnosticSeverity::HINT,
+                        Some(vec![DiagnosticTag::UNNECESSARY]),
+                    ),
+                };
+                (t, severity, tags)
+            })
+            .map(|(t, severity, tags)| Diagnostic {
                 range: Range {
                     start: Position::new(t.start.line, t.start.col),
                     end: Position::new(t.end.line, t.end.col),
                 },
-                severity: Some(severity.to_lsp_diagnostic()),
+                severity: Some(severity),
+                tags,
                 code: Some(NumberOrString::Number(1)),
-                message: format!("Unknown word \"{}\"", t.lexeme),
+                code_description: code_description(&t.lexeme),
+                source: Some(source.clone()),
+                message: message(&t.lexeme),
                 data: Some(Value::String(t.lexeme.to_string())),
                 ..Default::default()
             })
-            .collect()
+            .collect::<Vec<_>>();
+        // Thousands of diagnostics, e.g. in a minified file, lock up the editor
+        if let Some((first_omitted, _)) = misspellings.get(max_diagnostics) {
+            let start = Position::new(first_omitted.start.line, first_omitted.start.col);
+            diagnostics.push(Diagnostic {
+                range: Range { start, end: start },
+                severity: Some(DiagnosticSeverity::INFORMATION),
+                source: Some(source),
+                message: format!(
+                    "Only the first {max_diagnostics} of {} misspellings are reported",
+                    misspellings.len()
+                ),
+                ..Default::default()
+            });
+        }
+        diagnostics
     }
 
-    async fn add_all_to_dict(&self, params: ExecuteCommandParams) {
+    async fn add_all_to_dict(&self, params: ExecuteCommandParams) -> Option<Value> {
         info!("Adding all spelling mistakes to local dict");
         let [Value::String(uri)] = &params.arguments.as_slice() else {
-            return;
+            return None;
         };
-        let Ok(uri) = Url::from_str(uri) else { return };
-        let Some(source) = self.sources.get(&uri) else {
-            return;
+        let Ok(uri) = Url::from_str(uri) else {
+            return None;
+        };
+        let misspelled_words = {
+            let source = self.sources.get(&uri)?;
+            let folder = self.folder_of(&uri);
+            self.misspelled_tokens(&source, &self.language_id(&uri), folder.as_deref())
+                .into_iter()
+                .map(|t| t.lexeme)
+                .collect::<HashSet<_>>()
         };
-        let misspelled_words = self
-            .misspelled_tokens(&source)
-            .into_iter()
-            .map(|t| t.lexeme)
-            .collect::<HashSet<_>>();
+
+        if !self.config.read().add_all_immediately {
+            // The client confirms which words to add via add.all.confirm
+            let mut words = misspelled_words.into_iter().collect::<Vec<_>>();
+            words.sort();
+            return Some(Value::from(words));
+        }
 
         for word in misspelled_words {
             self.insert_into_local_dict(&word);
         }
         self.spell_check_uri(uri).await;
+        None
+    }
+
+    /// The misspelled words of a document with how often they occur,
+    /// e.g. to pick the ones to add through add.all.confirm
+    fn list_misspelled(&self, params: ExecuteCommandParams) -> Option<Value> {
+        let [Value::String(uri)] = &params.arguments.as_slice() else {
+            return None;
+        };
+        let uri = Url::from_str(uri).ok()?;
+        let source = self.sources.get(&uri)?;
+        let folder = self.folder_of(&uri);
+        let mut counts = BTreeMap::<String, usize>::new();
+        for token in self.misspelled_tokens(&source, &self.language_id(&uri), folder.as_deref()) {
+            *counts.entry(token.lexeme).or_default() += 1;
+        }
+        let words = counts
+            .into_iter()
+            .map(|(word, count)| serde_json::json!({ "word": word, "count": count }))
+            .collect::<Vec<_>>();
+        Some(Value::from(words))
+    }
+
+    async fn confirm_add_all(&self, params: ExecuteCommandParams) {
+        info!("Adding confirmed spelling mistakes to local dict");
+        let [Value::String(uri), Value::Array(words)] = &params.arguments.as_slice() else {
+            return;
+        };
+        for word in words.iter().filter_map(|w| w.as_str()) {
+            self.insert_into_local_dict(word);
+        }
+        let Ok(uri) = Url::from_str(uri) else { return };
+        self.spell_check_uri(uri).await;
     }
 
     async fn replace_with_word(&self, params: ExecuteCommandParams) {
@@ -104,17 +691,38 @@ impl Backend {
         let range = serde_json::from_value::<Range>(range.to_owned())
             .expect("Could not convert argument to range");
         let Ok(uri) = Url::from_str(uri) else { return };
-        self.replace_word_in_source(&uri, &range, word);
-        self.spell_check_uri(uri).await
+        // The edit is applied before the re-check so it sees the new text
+        // even if the client's did_change arrives later
+        if self.replace_word_in_source(&uri, &range, word) {
+            self.spell_check_uri(uri).await
+        }
     }
 
-    fn replace_word_in_source(&self, uri: &Url, range: &Range, word: &str) {
-        let Some(mut source) = self.sources.get_mut(&uri) else {
-            return;
+    /// Misspelled tokens of the document spelled like `word` in any casing
+    fn occurrences(&self, uri: &Url, word: &str) -> Vec<Token> {
+        let Some(source) = self.sources.get(uri) else {
+            return vec![];
+        };
+        let word = word.to_lowercase();
+        let folder = self.folder_of(uri);
+        self.misspelled_tokens(&source, &self.language_id(uri), folder.as_deref())
+            .into_iter()
+            .filter(|t| t.lexeme.to_lowercase() == word)
+            .collect()
+    }
+
+    /// Returns false if the document isn't open
+    fn replace_word_in_source(&self, uri: &Url, range: &Range, word: &str) -> bool {
+        let Some(mut source) = self.sources.get_mut(uri) else {
+            return false;
         };
-        let start = source.byte_of_line(range.start.line as usize) + range.start.character as usize;
-        let end = start + word.bytes().count();
-        source.replace(start..end, word);
+        let encoding = *self.position_encoding.read();
+        let start = byte_offset_for_position(&source, &range.start.into(), encoding);
+        let end = byte_offset_for_position(&source, &range.end.into(), encoding);
+        // Suggestions are lowercase, the casing of the replaced word is kept
+        let original = source.byte_slice(start..end).to_string();
+        source.replace(start..end, match_case(&original, word));
+        true
     }
 
     async fn add_to_dict(&self, params: ExecuteCommandParams) {
@@ -127,251 +735,1022 @@ impl Backend {
         self.spell_check_uri(uri).await;
     }
 
-    async fn spell_check_uri(&self, uri: Url) {
-        let Some(source) = self.sources.get(&uri) else {
+    async fn ignore_for_session(&self, params: ExecuteCommandParams) {
+        info!("Ignoring word for this session");
+        let [Value::String(word), Value::String(uri)] = &params.arguments.as_slice() else {
             return;
         };
-        let diagnostics = self.spell_check_code(&source);
-        self.client
-            .publish_diagnostics(uri, diagnostics, None)
-            .await;
+        self.session_words.insert(word.to_string());
+        let Ok(uri) = Url::from_str(uri) else { return };
+        self.spell_check_uri(uri).await;
     }
 
-    fn load_local_dict_from_file(&self) {
-        let config = &self.config.read();
-        if !config.dict_path.exists() {
+    async fn add_to_workspace_dict(&self, params: ExecuteCommandParams) {
+        info!("Adding word to workspace dictionary");
+        let [Value::String(word), Value::String(uri)] = &params.arguments.as_slice() else {
             return;
         };
-        let file = fs::read_to_string(&config.dict_path).expect("Unable to read dict");
-        for w in file.split("\n") {
-            self.local_dict.insert(w.to_string());
-        }
+        let Ok(uri) = Url::from_str(uri) else { return };
+        self.insert_into_workspace_dict(&uri, word);
+        self.spell_check_uri(uri).await;
     }
 
-    fn insert_into_local_dict(&self, word: &str) {
-        self.local_dict.insert(word.to_string());
-        let path = &self.config.read().dict_path;
-        if !path.exists() {
-            if let Some(parent) = path.parent() {
-                fs::create_dir_all(parent).expect("Unable to create config dir");
-            }
+    async fn remove_from_dict(&self, params: ExecuteCommandParams) {
+        info!("Removing word from local dictionary");
+        let [Value::String(word), Value::String(uri)] = &params.arguments.as_slice() else {
+            return;
+        };
+        self.remove_from_local_dict(word);
+        let Ok(uri) = Url::from_str(uri) else { return };
+        self.spell_check_uri(uri).await;
+    }
+
+    async fn export_state(&self) -> Option<Value> {
+        info!("Exporting local dictionary state");
+        let state = DictionaryState {
+            words: self.local_dict.words(),
+            workspace_words: self
+                .workspace_dicts
+                .iter()
+                .map(|e| (e.key().clone(), e.value().words()))
+                .collect(),
+            session_words: self.session_words.words(),
         };
+        match serde_json::to_value(state) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                self.log_error(format!("Unable to export state: {e}")).await;
+                None
+            }
+        }
+    }
 
-        let mut file = OpenOptions::new()
-            .append(true)
-            .create(true) // Create if it doesn't exist
-            .open(path)
-            .expect("Unable to open local dictionary");
+    /// The words of the user dictionary, one per line. Also written to the
+    /// path given as argument, if any.
+    async fn export_dict(&self, params: ExecuteCommandParams) -> Option<Value> {
+        info!("Exporting local dictionary");
+        let content = self
+            .local_dict
+            .words()
+            .iter()
+            .map(|w| format!("{w}\n"))
+            .collect::<String>();
+        if let [Value::String(path)] = &params.arguments.as_slice() {
+            let path = expand_tilde(path)?;
+            if let Err(e) = fs::write(&path, &content) {
+                self.log_error(format!("Unable to export dictionary to {path:?}: {e}"))
+                    .await;
+                return None;
+            }
+        }
+        Some(Value::String(content))
+    }
 
-        writeln!(file, "{word}").expect("Unable to append to local dictionary");
+    /// Adds the words of the dictionary file given as argument to the user
+    /// dictionary
+    async fn import_dict(&self, params: ExecuteCommandParams) {
+        info!("Importing dictionary file");
+        let [Value::String(path)] = &params.arguments.as_slice() else {
+            return;
+        };
+        let Some(path) = expand_tilde(path) else {
+            return;
+        };
+        let imported = LocalDictionary::new();
+        if let Err(e) = imported.load_file(&path) {
+            self.log_error(format!("Unable to import dictionary {path:?}: {e}"))
+                .await;
+            return;
+        }
+        for word in imported.words() {
+            if !self.local_dict.contains(&word) {
+                self.insert_into_local_dict(&word);
+            }
+        }
+        self.spell_check_all().await;
     }
 
-    async fn load_config(&self, init: InitializeParams) {
-        let Some(options) = init.initialization_options else {
+    async fn import_state(&self, params: ExecuteCommandParams) {
+        info!("Importing local dictionary state");
+        let [state, Value::String(mode)] = &params.arguments.as_slice() else {
             return;
         };
-        let mut options: Config = match serde_json::from_value(options) {
-            Ok(o) => o,
+        let state: DictionaryState = match serde_json::from_value(state.to_owned()) {
+            Ok(state) => state,
             Err(e) => {
-                self.log_error(e).await;
+                self.log_error(format!("Invalid dictionary state: {e}"))
+                    .await;
                 return;
             }
         };
-        options.dict_path = expand_tilde(options.dict_path).expect("Invalid dict path");
-        *self.config.write() = options;
+        let replace = match mode.as_str() {
+            "merge" => false,
+            "replace" => true,
+            _ => {
+                self.log_error(format!(
+                    "Unknown import mode \"{mode}\", expected \"merge\" or \"replace\""
+                ))
+                .await;
+                return;
+            }
+        };
+        if replace {
+            self.replace_local_dict(&state.words);
+            self.session_words.clear();
+        } else {
+            for word in &state.words {
+                self.insert_into_local_dict(word);
+            }
+        }
+        for word in state.session_words {
+            self.session_words.insert(word);
+        }
+        let folders = { self.workspace_folders.read().clone() };
+        for (folder, words) in &state.workspace_words {
+            // Folders are only restored into the same workspace
+            if !folders.contains(folder) {
+                info!("Skipping the words of {folder:?}, it isn't a workspace folder");
+                continue;
+            }
+            if replace {
+                self.replace_workspace_dict(folder, words);
+            } else {
+                for word in words {
+                    let known = self
+                        .workspace_dicts
+                        .get(folder)
+                        .is_some_and(|d| d.contains(word));
+                    if !known {
+                        self.insert_into_folder_dict(folder, word);
+                    }
+                }
+            }
+        }
+        self.spell_check_all().await;
     }
 
-    async fn log_error<T: Display>(&self, v: T) {
-        self.client.log_message(MessageType::ERROR, v).await
+    /// Publishes diagnostics for every file in the workspace that isn't open
+    async fn check_workspace(&self) {
+        info!("Checking workspace");
+        let root = { self.workspace_root.read().clone() };
+        let respect_gitignore = { self.config.read().respect_gitignore };
+        let Some(root) = root else {
+            return;
+        };
+        for path in workspace::files(&root, respect_gitignore) {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            // Open documents are checked on every change already
+            if self.sources.contains_key(&uri) || !self.is_checked_file(&uri) {
+                continue;
+            }
+            // Binary files are not valid UTF-8
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let folder = self.folder_of(&uri);
+            let diagnostics = self.spell_check_code(&Rope::from(text), "", folder.as_deref());
+            self.publish_diagnostics(uri, diagnostics).await;
+        }
     }
 
-    async fn start_spellchecker(&self) {
-        let (suggester, suggester_tx) = mpsc::channel::<(String, oneshot::Sender<Vec<String>>)>();
-        *self.suggester.write() = Some(suggester);
-        let dicts = { self.config.read().dictionaries.clone() };
+    async fn spell_check_all(&self) {
+        if *self.pull_diagnostics.read() {
+            self.refresh_diagnostics().await;
+            return;
+        }
+        let uris = self
+            .sources
+            .iter()
+            .map(|e| e.key().clone())
+            .collect::<Vec<_>>();
+        for uri in uris {
+            self.spell_check_uri(uri).await;
+        }
+    }
 
-        let mut paths = Vec::with_capacity(dicts.len());
+    async fn spell_check_uri(&self, uri: Url) {
+        if *self.pull_diagnostics.read() {
+            self.refresh_diagnostics().await;
+            return;
+        }
+        let Some(source) = self.sources.get(&uri).map(|s| s.clone()) else {
+            return;
+        };
+        let Some(diagnostics) = self.document_diagnostics_until_changed(&uri, &source).await else {
+            debug!("Dropping outdated diagnostics of {uri}");
+            return;
+        };
+        self.publish_diagnostics(uri, diagnostics).await;
+    }
 
-        for dict in dicts {
-            let path = dict.resolve().await;
-            paths.push(path);
+    /// Diagnostics of an open document like `document_diagnostics`, checked
+    /// in chunks of words. Between them the handling of a newer change gets
+    /// to run, the check is abandoned with `None` once it did since the check
+    /// of that change publishes the diagnostics instead.
+    async fn document_diagnostics_until_changed(
+        &self,
+        uri: &Url,
+        source: &SourceCode,
+    ) -> Option<Vec<Diagnostic>> {
+        let change_count = self.change_count(uri);
+        if !self.is_checked_source(uri, source) {
+            return Some(vec![]);
+        }
+        // Remembered for the word mode checks of the following edits
+        let has_directives = !Directives::new(source).is_empty();
+        self.has_directives.insert(uri.clone(), has_directives);
+        let folder = self.folder_of(uri);
+        let language_id = self.language_id(uri);
+        let candidates = self.candidates(source, &language_id, folder.as_deref(), None);
+        let mut misspellings = vec![];
+        for chunk in candidates.chunks(CHECK_CHUNK_WORDS) {
+            misspellings.extend(self.check_candidates(chunk.to_vec(), &language_id));
+            tokio::task::yield_now().await;
+            if self.change_count(uri) != change_count {
+                return None;
+            }
         }
+        Some(self.diagnostics_of(&misspellings, folder.as_deref()))
+    }
 
-        let suggestion_paths = paths.clone();
+    fn change_count(&self, uri: &Url) -> u64 {
+        self.change_counts.get(uri).map(|c| *c).unwrap_or_default()
+    }
 
-        thread::spawn(move || {
-            let checkers: Vec<_> = suggestion_paths
-                .iter()
-                .map(|p| Hunspell::new(p.aff.to_str().unwrap(), p.dic.to_str().unwrap()))
-                .collect();
+    /// Diagnostics of an open document, none if the document isn't checked
+    fn document_diagnostics(&self, uri: &Url, source: &SourceCode) -> Vec<Diagnostic> {
+        if !self.is_checked_source(uri, source) {
+            return vec![];
+        }
+        let folder = self.folder_of(uri);
+        self.spell_check_code(source, &self.language_id(uri), folder.as_deref())
+    }
 
-            while let Ok((word, send)) = suggester_tx.recv() {
-                let suggestions = checkers
-                    .iter()
-                    .flat_map(|c| c.suggest(&word))
-                    // Suggestions shorter than 2 characters are usually bad
-                    .filter(|s| s.len() > 2)
-                    // remove duplicates
-                    .collect::<HashSet<_>>()
-                    .into_iter()
-                    // Take at most 6 suggestions
-                    // TODO: Make this better
-                    .take(6)
-                    .collect::<Vec<_>>();
+    fn is_checked_source(&self, uri: &Url, source: &SourceCode) -> bool {
+        if !self.is_checked_file(uri) {
+            return false;
+        }
+        let max_file_bytes = { self.config.read().max_file_bytes };
+        if source.byte_len() > max_file_bytes {
+            info!("Not checking {uri}, it's larger than max_file_bytes ({max_file_bytes})");
+            return false;
+        }
+        true
+    }
 
-                let _ = send.send(suggestions);
-            }
-        });
-        let (checker, checker_tx) = mpsc::channel::<(String, oneshot::Sender<bool>)>();
-        *self.checker.write() = Some(checker);
+    /// Makes a client pulling diagnostics ask for the ones of every open
+    /// document again, e.g. after a word was added to a dictionary
+    async fn refresh_diagnostics(&self) {
+        if let Err(e) = self.client.workspace_diagnostic_refresh().await {
+            warn!("Unable to refresh diagnostics: {e}");
+        }
+    }
 
-        thread::spawn(move || {
-            let checkers: Vec<_> = paths
-                .iter()
-                .map(|p| Hunspell::new(p.aff.to_str().unwrap(), p.dic.to_str().unwrap()))
-                .collect();
-            while let Ok((word, send)) = checker_tx.recv() {
-                let result = &checkers
-                    .iter()
-                    .any(|c| c.check(&word) == CheckResult::FoundInDictionary);
-                let _ = send.send(*result);
+    /// Re-checks only the words touched by `change`, the edit from `previous`
+    /// to the current source. Returns false if the edit can't be checked that
+    /// way.
+    async fn spell_check_edited_words(
+        &self,
+        uri: &Url,
+        previous: &SourceCode,
+        change: &TextDocumentContentChangeEvent,
+    ) -> bool {
+        // An edit can move the words after it in or out of a comment or string
+        let (check_scope, ignore_ordinals, extra_word_chars) = {
+            let config = self.config.read();
+            (
+                config.check_scope,
+                config.ignore_ordinals,
+                config.extra_word_chars.chars().collect::<Vec<_>>(),
+            )
+        };
+        if check_scope != CheckScope::All || !self.is_checked_file(uri) {
+            return false;
+        }
+        let encoding = *self.position_encoding.read();
+        let Some(edit) = LineEdit::from_change(change, encoding) else {
+            return false;
+        };
+        // Directives apply beyond the edited words, the ones of the document
+        // were found by its last full check
+        let has_directives = self.has_directives.get(uri).is_none_or(|d| *d);
+        let edited_line = edit.line as usize;
+        if has_directives
+            || edited_line >= previous.line_len()
+            || Directives::in_line(&previous.line(edited_line).to_string())
+        {
+            return false;
+        }
+        let diagnostics = {
+            let Some(source) = self.sources.get(uri) else {
+                return false;
+            };
+            let Some(published) = self.diagnostics.get(uri) else {
+                return false;
+            };
+            // Words past the cap were never reported, only a full check finds them
+            if published.len() > self.config.read().max_diagnostics_per_file {
+                return false;
             }
+            if Directives::in_line(&source.line(edited_line).to_string()) {
+                return false;
+            }
+            let tokens = edit.edited_tokens(&source, encoding, ignore_ordinals, &extra_word_chars);
+            let folder = self.folder_of(uri);
+            let edited_diagnostics = self.spell_check_tokens(
+                &source,
+                &self.language_id(uri),
+                folder.as_deref(),
+                Some(&tokens),
+            );
+            edit.merge_diagnostics(&published, &tokens, edited_diagnostics)
+        };
+        self.publish_diagnostics(uri.clone(), diagnostics).await;
+        true
+    }
+
+    /// Waits `debounce_ms` for further changes to `uri`. Returns false if
+    /// another change arrived meanwhile, the check is left to that change so
+    /// the last one always gets its pass.
+    async fn debounce(&self, uri: &Url) -> bool {
+        let debounce = { Duration::from_millis(self.config.read().debounce_ms) };
+        if debounce.is_zero() {
+            return true;
+        }
+        let (done, finished) = tokio::sync::oneshot::channel();
+        let timer = tokio::spawn(async move {
+            tokio::time::sleep(debounce).await;
+            let _ = done.send(());
         });
+        if let Some(previous) = self.debounce_timers.insert(uri.clone(), timer) {
+            previous.abort();
+        }
+        // An aborted timer drops `done`
+        finished.await.is_ok()
     }
 
-    fn spell_check(&self, word: &str) -> bool {
-        let (rx, tx) = oneshot::channel();
-        let checker = self.checker.read();
-        let _ = checker.as_ref().unwrap().send((word.to_string(), rx));
-        tx.recv().unwrap_or(true)
+    async fn publish_diagnostics(&self, uri: Url, diagnostics: Vec<Diagnostic>) {
+        let diagnostics = self.store_diagnostics(&uri, diagnostics);
+        self.client
+            .publish_diagnostics(uri, diagnostics, None)
+            .await;
     }
 
-    fn suggest(&self, word: &str) -> Vec<String> {
-        let (rx, tx) = oneshot::channel();
-        let suggester = self.suggester.read();
-        let _ = suggester.as_ref().unwrap().send((word.to_string(), rx));
-        tx.recv().unwrap_or(vec![])
+    /// Stores the diagnostics for code actions and hovers and returns the
+    /// ones shown to the user
+    fn store_diagnostics(&self, uri: &Url, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        self.diagnostics.insert(uri.clone(), diagnostics.clone());
+        // The suppressed diagnostics are still stored so they show up once the
+        // user is done typing the word
+        let diagnostics = self.without_typed_word(uri, diagnostics);
+        let group_by_word = { self.config.read().group_by_word };
+        if group_by_word {
+            grouped_by_word(uri, diagnostics)
+        } else {
+            with_related_occurrences(uri, diagnostics)
+        }
     }
-}
 
-#[tower_lsp::async_trait]
-impl LanguageServer for Backend {
-    async fn initialize(&self, init: InitializeParams) -> Result<InitializeResult> {
-        self.load_config(init).await;
-        self.load_local_dict_from_file();
-        self.start_spellchecker().await;
+    /// Drops the diagnostic of the word being typed, i.e. the word touching
+    /// the cursor. Typing a space or punctuation moves the cursor off the
+    /// word which releases it.
+    fn without_typed_word(&self, uri: &Url, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let Some(cursor) = self.typing_cursors.get(uri).map(|c| *c) else {
+            return diagnostics;
+        };
+        diagnostics
+            .into_iter()
+            .filter(|d| {
+                d.range.start.line != cursor.line
+                    || cursor.col < d.range.start.character
+                    || d.range.end.character < cursor.col
+            })
+            .collect()
+    }
 
-        Ok(InitializeResult {
-            server_info: Some(ServerInfo {
-                name: "Rustproof".to_string(),
-                version: Some(self.version.to_string()),
-            }),
-            offset_encoding: None,
-            capabilities: ServerCapabilities {
-                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
-                text_document_sync: Some(TextDocumentSyncCapability::Options(
-                    TextDocumentSyncOptions {
-                        open_close: Some(true),
-                        change: Some(TextDocumentSyncKind::FULL),
+    fn language_id(&self, uri: &Url) -> String {
+        self.languages
+            .get(uri)
+            .map(|l| l.to_string())
+            .unwrap_or_default()
+    }
 
-                        ..Default::default()
-                    },
-                )),
-                execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec![
-                        "replace.with.word".to_string(),
-                        "add.to.dict".to_string(),
-                        "add.all.to.dict".to_string(),
-                    ],
-                    ..Default::default()
-                }),
-                ..Default::default()
-            },
-        })
+    fn is_checked_file(&self, uri: &Url) -> bool {
+        let Ok(path) = uri.to_file_path() else {
+            return true;
+        };
+        // Globs are matched relative to the workspace root
+        let root = self.workspace_root.read();
+        let path = root
+            .as_ref()
+            .and_then(|r| path.strip_prefix(r).ok())
+            .unwrap_or(path.as_path());
+        self.file_filter.read().is_checked(path)
     }
 
-    async fn shutdown(&self) -> Result<()> {
-        info!("shutdown");
-        Ok(())
+    /// The innermost workspace folder containing the document
+    fn folder_of(&self, uri: &Url) -> Option<PathBuf> {
+        let path = uri.to_file_path().ok()?;
+        self.workspace_folders
+            .read()
+            .iter()
+            .filter(|f| path.starts_with(f))
+            .max_by_key(|f| f.components().count())
+            .cloned()
     }
 
-    async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        info!("opened file");
-        let source = Rope::from(params.text_document.text);
-        let uri = params.text_document.uri;
-        self.sources.insert(uri.clone(), source);
-        self.spell_check_uri(uri).await;
+    /// Whether the user dictionary or the one of the workspace `folder` knows `word`
+    fn in_local_dicts(&self, word: &str, folder: Option<&Path>) -> bool {
+        self.local_dict.contains(word)
+            || self.config_words.contains(word)
+            || self.session_words.contains(word)
+            || folder
+                .and_then(|f| self.workspace_dicts.get(f))
+                .is_some_and(|d| d.contains(word))
     }
 
-    async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        info!("closed file");
-        self.sources.remove(&params.text_document.uri);
+    /// `workspace_dict_path` resolved against a workspace folder
+    fn workspace_dict_path(&self, folder: &Path) -> PathBuf {
+        folder.join(&self.config.read().workspace_dict_path)
     }
 
-    async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let Some(text) = params.content_changes.first().map(|c| &c.text) else {
+    fn load_local_dict_from_file(&self) {
+        let path = { self.config.read().dict_path.clone() };
+        load_dict_file(&self.local_dict, &path);
+        clean_dict_file_once(&path);
+        let folders = { self.workspace_folders.read().clone() };
+        for folder in folders {
+            let dict = LocalDictionary::new();
+            load_dict_file(&dict, &self.workspace_dict_path(&folder));
+            self.workspace_dicts.insert(folder, dict);
+        }
+    }
+
+    /// Replaces the words of the user and workspace dictionaries with the
+    /// ones in their files
+    fn reload_local_dicts(&self) {
+        self.local_dict.clear();
+        self.workspace_dicts.clear();
+        self.load_local_dict_from_file();
+    }
+
+    /// The user and workspace dictionary files
+    fn dict_files(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.config.read().dict_path.clone()];
+        let folders = { self.workspace_folders.read().clone() };
+        paths.extend(folders.iter().map(|f| self.workspace_dict_path(f)));
+        paths
+    }
+
+    /// Asks the client to report changes of the dictionary files, e.g. when
+    /// they are edited by hand or updated by a `git pull`
+    async fn watch_dict_files(&self) {
+        let watchers = self
+            .dict_files()
+            .iter()
+            .map(|path| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(path.to_string_lossy().to_string()),
+                kind: None,
+            })
+            .collect();
+        let registration = Registration {
+            id: DICT_FILES_REGISTRATION.to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers,
+            })
+            .ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            warn!("Unable to watch the dictionary files: {e}");
+        }
+    }
+
+    /// Stops watching the dictionary files, e.g. before watching the ones
+    /// of a changed configuration
+    async fn unwatch_dict_files(&self) {
+        let unregistration = Unregistration {
+            id: DICT_FILES_REGISTRATION.to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+        };
+        if let Err(e) = self
+            .client
+            .unregister_capability(vec![unregistration])
+            .await
+        {
+            warn!("Unable to stop watching the dictionary files: {e}");
+        }
+    }
+
+    /// Sorts and de-duplicates the user and workspace dictionary files
+    fn clean_dict_files(&self) {
+        info!("Cleaning dictionary files");
+        for path in self.dict_files() {
+            if path.exists() {
+                clean_dict_file(&path);
+            }
+        }
+    }
+
+    fn insert_into_local_dict(&self, word: &str) {
+        self.local_dict.insert(word.to_string());
+        let path = &self.config.read().dict_path;
+        append_to_dict_file(path, word);
+    }
+
+    /// Adds `word` to the dictionary of the workspace folder of the document,
+    /// does nothing for documents outside of the workspace
+    fn insert_into_workspace_dict(&self, uri: &Url, word: &str) {
+        let Some(folder) = self.folder_of(uri) else {
             return;
         };
-        let source = Rope::from(text.to_owned());
-        let uri = params.text_document.uri;
-        self.sources.insert(uri.clone(), source);
-        self.spell_check_uri(uri).await
+        self.insert_into_folder_dict(&folder, word);
     }
 
-    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        let uri = params.text_document.uri;
-        let cursor_line = params.range.start.line;
-        let cursor_col = params.range.start.character;
-        let diagnostic_under_cursor = params.context.diagnostics.iter().find(|d| {
-            d.range.start.line == cursor_line
-                && d.range.start.character <= cursor_col
-                && cursor_col < d.range.end.character
+    fn insert_into_folder_dict(&self, folder: &Path, word: &str) {
+        self.workspace_dicts
+            .entry(folder.to_path_buf())
+            .or_insert_with(LocalDictionary::new)
+            .insert(word.to_string());
+        append_to_dict_file(&self.workspace_dict_path(folder), word);
+    }
+
+    fn remove_from_local_dict(&self, word: &str) {
+        if !self.local_dict.remove(word) {
+            return;
+        }
+        let path = &self.config.read().dict_path;
+        let Ok(content) = fs::read_to_string(path) else {
+            return;
+        };
+        let word = word.to_lowercase();
+        let content = content
+            .lines()
+            .filter(|l| l.to_lowercase() != word)
+            .map(|l| format!("{l}\n"))
+            .collect::<String>();
+        // Write to a temporary file first so a crash can't truncate the dictionary
+        let tmp = path.with_extension("tmp");
+        if let Err(e) = fs::write(&tmp, content).and_then(|_| fs::rename(&tmp, path)) {
+            error!("Unable to remove {word:?} from dictionary {path:?}: {e}");
+        }
+    }
+
+    fn replace_local_dict(&self, words: &[String]) {
+        self.local_dict.clear();
+        for word in words {
+            self.local_dict.insert(word.to_string());
+        }
+        let path = { self.config.read().dict_path.clone() };
+        write_dict_file(&path, words);
+    }
+
+    fn replace_workspace_dict(&self, folder: &Path, words: &[String]) {
+        let dict = LocalDictionary::new();
+        for word in words {
+            dict.insert(word.to_string());
+        }
+        self.workspace_dicts.insert(folder.to_path_buf(), dict);
+        write_dict_file(&self.workspace_dict_path(folder), words);
+    }
+
+    async fn load_config(&self, init: InitializeParams) {
+        self.apply_settings(init.initialization_options).await;
+    }
+
+    /// Replaces the config with the user's `settings` layered under the
+    /// project config and over the word lists of a CSpell config.
+    /// Invalid settings are logged and the config is kept.
+    async fn apply_settings(&self, user: Option<Value>) {
+        let cspell = self.load_cspell_config().await;
+        let project = self.load_project_config().await;
+        let Some(options) = [cspell, user, project]
+            .into_iter()
+            .flatten()
+            .reduce(merge_layers)
+        else {
+            return;
+        };
+        let mut options: Config = match serde_json::from_value(options) {
+            Ok(o) => o,
+            Err(e) => {
+                self.log_error(e).await;
+                return;
+            }
+        };
+        options.dict_path = expand_tilde(options.dict_path).expect("Invalid dict path");
+        options.frequency_list = options.frequency_list.and_then(expand_tilde);
+        options.apply_language_variant();
+        match FileFilter::new(&options.include_files, &options.ignore_files) {
+            Ok(filter) => *self.file_filter.write() = filter,
+            Err(e) => self.log_error(format!("Invalid file glob: {e}")).await,
+        };
+        let mut ignore_regexes = vec![];
+        for pattern in &options.ignore_regex {
+            match Regex::new(pattern) {
+                Ok(regex) => ignore_regexes.push(regex),
+                Err(e) => self.log_error(format!("Invalid ignore regex: {e}")).await,
+            }
+        }
+        *self.ignore_regexes.write() = ignore_regexes;
+        if let Some(Err(e)) = options.explanation_url.as_deref().map(Url::parse) {
+            self.log_error(format!("Invalid explanation url: {e}"))
+                .await;
+        }
+        *self.flagged_words.write() = options.flagged_words();
+        self.config_words.clear();
+        for word in &options.words {
+            self.config_words.insert(word.to_string());
+        }
+        *self.config.write() = options;
+    }
+
+    /// The project config file in the workspace root, layered over the
+    /// initialization options by `merge_layers`
+    async fn load_project_config(&self) -> Option<Value> {
+        let (name, content) = {
+            let root = self.workspace_root.read();
+            let root = root.as_ref()?;
+            PROJECT_CONFIG_FILES
+                .iter()
+                .find_map(|name| Some((*name, fs::read_to_string(root.join(name)).ok()?)))?
+        };
+        let config = if name.ends_with(".json") {
+            serde_json::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        };
+        match config {
+            Ok(config) => Some(config),
+            Err(e) => {
+                self.log_error(format!("Invalid {name}: {e}")).await;
+                None
+            }
+        }
+    }
+
+    /// The word lists of a CSpell config in the workspace root, layered
+    /// under the initialization options to ease migrating from CSpell
+    async fn load_cspell_config(&self) -> Option<Value> {
+        let (name, content) = {
+            let root = self.workspace_root.read();
+            let root = root.as_ref()?;
+            CSPELL_CONFIG_FILES
+                .iter()
+                .find_map(|name| Some((*name, fs::read_to_string(root.join(name)).ok()?)))?
+        };
+        match cspell_layer(&content) {
+            Ok(layer) => Some(layer),
+            Err(e) => {
+                self.log_error(format!("Invalid {name}: {e}")).await;
+                None
+            }
+        }
+    }
+
+    /// Downloads the dictionaries again and restarts the checker with them
+    async fn update_dictionaries(&self) {
+        let dicts = { self.config.read().dictionaries.clone() };
+        for dict in &dicts {
+            self.client
+                .log_message(
+                    MessageType::INFO,
+                    format!("Updating dictionary \"{}\"", dict.language),
+                )
+                .await;
+            dict.clear_cache().await;
+        }
+        // Resolving the dictionaries downloads them again
+        self.start_spellchecker().await;
+        self.spell_check_all().await;
+        self.client
+            .log_message(MessageType::INFO, "Updated dictionaries")
+            .await;
+    }
+
+    async fn status(&self) -> Option<Value> {
+        let config = { serde_json::to_value(&*self.config.read()) };
+        match config {
+            Ok(config) => Some(serde_json::json!({ "version": self.version, "config": config })),
+            Err(e) => {
+                self.log_error(format!("Unable to serialize config: {e}"))
+                    .await;
+                None
+            }
+        }
+    }
+
+    async fn log_error<T: Display>(&self, v: T) {
+        // Also seen when there is no client, e.g. in `rustproof check`
+        error!("{v}");
+        self.client.log_message(MessageType::ERROR, v).await
+    }
+
+    async fn start_spellchecker(&self) {
+        let (dicts, download_timeout, checker_threads) = {
+            let config = self.config.read();
+            (
+                config.dictionaries.clone(),
+                Duration::from_millis(config.download_timeout_ms),
+                config.checker_threads,
+            )
+        };
+
+        let work_done_progress = { *self.work_done_progress.read() };
+        let progress = if work_done_progress {
+            WorkDone::begin(&self.client, "rustproof/dictionaries", "Rustproof").await
+        } else {
+            None
+        };
+
+        let mut paths = Vec::with_capacity(dicts.len());
+        let mut languages = Vec::with_capacity(dicts.len());
+
+        for dict in dicts {
+            if let Some(progress) = &progress {
+                progress
+                    .report(format!("Fetching dictionary \"{}\"", dict.language))
+                    .await;
+            }
+            match dict.resolve(download_timeout).await {
+                Ok(path) => {
+                    paths.push(path);
+                    languages.push(dict.language);
+                }
+                // The other dictionaries still work
+                Err(e) => {
+                    self.log_error(format!("Skipping dictionary \"{}\": {e}", dict.language))
+                        .await
+                }
+            }
+        }
+
+        let suggestion_engine = { self.config.read().suggestion_engine };
+        let ranker = self.suggestion_ranker().await;
+        // Answers of the previous dictionaries are stale
+        self.check_cache.clear();
+        self.suggestion_cache.clear();
+
+        let (requests, requests_rx) = mpsc::channel::<SpellRequest>();
+        *self.spellchecker.write() = Some(requests);
+
+        // The dictionaries are loaded once and serve both checks and
+        // suggestions on every checker thread
+        let (loading, mut loading_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        thread::spawn(move || {
+            let checkers = languages
+                .into_iter()
+                .zip(&paths)
+                .map(|(language, p)| {
+                    let _ = loading.send(language.clone());
+                    (language, Mutex::new(new_spellchecker(p)))
+                })
+                .collect();
+            drop(loading);
+            let spellcheckers = Spellcheckers {
+                checkers,
+                paths,
+                suggestion_engine,
+                ranker,
+                symspell: OnceLock::new(),
+            };
+            serve_spell_requests(checker_threads, requests_rx, Arc::new(spellcheckers));
         });
-        let Some(diagnostic_under_cursor) = diagnostic_under_cursor else {
-            return Ok(None);
+
+        let Some(progress) = progress else {
+            return;
+        };
+        // Requests are queued until the dictionaries are loaded, the server
+        // doesn't wait for them
+        tokio::spawn(async move {
+            while let Some(language) = loading_rx.recv().await {
+                progress
+                    .report(format!("Loading dictionary \"{language}\""))
+                    .await;
+            }
+            progress.end().await;
+        });
+    }
+
+    async fn suggestion_ranker(&self) -> SuggestionRanker {
+        let (tiebreak, frequency_list, proper_nouns) = {
+            let config = self.config.read();
+            (
+                config.suggestion_tiebreak,
+                config.frequency_list.clone(),
+                config.proper_noun_suggestions,
+            )
         };
+        let frequencies = match frequency_list.map(fs::read_to_string) {
+            Some(Ok(frequencies)) => frequencies,
+            Some(Err(e)) => {
+                self.log_error(format!("Unable to read frequency list: {e}"))
+                    .await;
+                String::new()
+            }
+            None => String::new(),
+        };
+        SuggestionRanker::new(tiebreak, &frequencies).proper_nouns(proper_nouns)
+    }
 
-        let Some(Value::String(word)) = diagnostic_under_cursor.data.as_ref() else {
-            return Ok(None);
+    fn spell_check(&self, word: &str, dictionaries: Option<&[String]>) -> bool {
+        self.check_word(word, dictionaries).0
+    }
+
+    /// Returns whether any of the dictionaries knows `word`
+    /// and the dictionaries that don't
+    fn check_word(&self, word: &str, dictionaries: Option<&[String]>) -> (bool, Vec<String>) {
+        self.check_words([word].into_iter(), dictionaries)
+            .pop()
+            .unwrap()
+    }
+
+    /// `check_word` for many words, all of them are sent before awaiting the
+    /// first answer so the spellchecker thread works through them in one go
+    fn check_words<'w>(
+        &self,
+        words: impl Iterator<Item = &'w str>,
+        dictionaries: Option<&[String]>,
+    ) -> Vec<(bool, Vec<String>)> {
+        let dictionaries = dictionaries.map(|d| d.to_vec());
+        let words = words.collect::<Vec<_>>();
+        let mut answers: HashMap<&str, (bool, Vec<String>)> = HashMap::new();
+        let mut pending = vec![];
+        {
+            let spellchecker = self.spellchecker.read();
+            let Some(spellchecker) = spellchecker.as_ref() else {
+                debug!("The spellchecker isn't running, treating words as correct");
+                return words.iter().map(|_| (true, vec![])).collect();
+            };
+            let mut seen = HashSet::new();
+            // Every distinct word is looked up once, cached words aren't sent at all
+            for word in words.iter().copied().filter(|w| seen.insert(*w)) {
+                let key = (word.to_string(), dictionaries.clone());
+                if let Some(cached) = self.check_cache.get(&key) {
+                    answers.insert(word, cached);
+                    continue;
+                }
+                let (rx, tx) = oneshot::channel();
+                let request = (word.to_string(), dictionaries.clone(), rx);
+                let _ = spellchecker.send(SpellRequest::Check(request));
+                pending.push((word, key, tx));
+            }
+        }
+        // The timeout covers the whole batch, not each of its words
+        let deadline = self.operation_deadline();
+        for (word, key, tx) in pending {
+            let checked = match self.recv_until(tx, deadline, word) {
+                Some(checked) => {
+                    self.check_cache.insert(key, checked.clone());
+                    checked
+                }
+                // A stuck checker shouldn't freeze the server, treat the word as correct
+                None => (true, vec![]),
+            };
+            answers.insert(word, checked);
+        }
+        words.iter().map(|w| answers[w].clone()).collect()
+    }
+
+    fn suggest(&self, word: &str, folder: Option<&Path>) -> Vec<String> {
+        let mut suggestions = match self.suggestion_cache.get(&word.to_string()) {
+            Some(suggestions) => suggestions,
+            None => {
+                let (rx, tx) = oneshot::channel();
+                {
+                    let spellchecker = self.spellchecker.read();
+                    let Some(spellchecker) = spellchecker.as_ref() else {
+                        debug!("The spellchecker isn't running, no suggestions for \"{word}\"");
+                        return vec![];
+                    };
+                    let _ = spellchecker.send(SpellRequest::Suggest((word.to_string(), rx)));
+                }
+                let suggestions = self.recv_until(tx, self.operation_deadline(), word);
+                if let Some(suggestions) = &suggestions {
+                    self.suggestion_cache
+                        .insert(word.to_string(), suggestions.clone());
+                }
+                suggestions.unwrap_or(vec![])
+            }
+        };
+        // Hunspell doesn't know the words the user taught us, suggest them for their own
+        // typos ahead of the first ranked suggestion that is further away
+        let near_words = self
+            .local_dict
+            .near(word, MAX_LOCAL_SUGGESTION_DISTANCE)
+            .into_iter()
+            .chain(self.config_words.near(word, MAX_LOCAL_SUGGESTION_DISTANCE))
+            .chain(
+                folder
+                    .and_then(|f| self.workspace_dicts.get(f))
+                    .map(|d| d.near(word, MAX_LOCAL_SUGGESTION_DISTANCE))
+                    .unwrap_or_default(),
+            );
+        for near in near_words {
+            if suggestions.contains(&near) {
+                continue;
+            }
+            let distance = edit_distance(word, &near);
+            let at = suggestions
+                .iter()
+                .position(|s| edit_distance(word, s) > distance)
+                .unwrap_or(suggestions.len());
+            suggestions.insert(at, near);
+        }
+        // The preferred spelling of a flagged word comes first
+        let preferred = { self.flagged_words.read().get(&word.to_lowercase()).cloned() };
+        if let Some(Some(preferred)) = preferred {
+            suggestions.retain(|s| *s != preferred);
+            suggestions.insert(0, preferred);
+        }
+        suggestions.truncate(self.config.read().max_suggestions);
+        suggestions
+    }
+
+    /// Suggests splitting a run-together word like `thequickbrown` into
+    /// dictionary words. Only offered where spaces are valid, i.e. in
+    /// comments and strings or documents without a known syntax.
+    fn split_suggestion(&self, uri: &Url, range: &Range, word: &str) -> Option<String> {
+        let language_id = self.language_id(uri);
+        let (prose_annotations, skip_format_placeholders, dictionaries) = {
+            let config = self.config.read();
+            (
+                config.prose_annotations.clone(),
+                config.skip_format_placeholders,
+                config.active_dictionaries(&language_id),
+            )
+        };
+        if let Some(syntax) = Syntax::for_language(&language_id) {
+            let source = self.sources.get(uri)?;
+            let scopes = Scopes::new(
+                syntax,
+                source.chars(),
+                &prose_annotations,
+                skip_format_placeholders,
+                *self.position_encoding.read(),
+            );
+            let start = Pos {
+                line: range.start.line,
+                col: range.start.character,
+            };
+            if scopes.scope_of(&start) == Scope::Code {
+                return None;
+            }
+        }
+        let folder = self.folder_of(uri);
+        let words = segment::segment(word, |w| {
+            self.in_local_dicts(w, folder.as_deref())
+                || self.spell_check(w, dictionaries.as_deref())
+        })?;
+        Some(words.join(" "))
+    }
+
+    /// Replacements and dictionary additions for the word of `diagnostic`
+    async fn quickfixes(&self, uri: &Url, diagnostic: &Diagnostic) -> Vec<CodeActionOrCommand> {
+        let Some(Value::String(word)) = diagnostic.data.as_ref() else {
+            return vec![];
         };
 
-        let mut code_actions = self
-            .suggest(word)
+        let range = diagnostic.range;
+        let folder = self.folder_of(uri);
+        // A cancelled request is dropped at its next await, yielding lets a
+        // `$/cancelRequest` that already arrived skip the suggestions
+        tokio::task::yield_now().await;
+        let suggestions = self.suggest(word, folder.as_deref());
+        let mut code_actions = suggestions
             .iter()
-            .map(|w| {
-                let title = format!("Replace with \"{}\"", w);
-                let mut changes = HashMap::new();
-                changes.insert(
-                    uri.clone(),
-                    vec![TextEdit {
-                        range: diagnostic_under_cursor.range,
-                        new_text: w.to_string(),
-                    }],
-                );
-                CodeActionOrCommand::CodeAction(CodeAction {
-                    title: title.to_string(),
-                    command: Some(Command {
-                        title,
-                        command: "replace.with.word".to_string(),
-                        arguments: Some(vec![
-                            Value::String(uri.to_string()),
-                            serde_json::to_value(diagnostic_under_cursor.range)
-                                .expect("Could not convert range to value"),
-                            Value::String(w.to_string()),
-                        ]),
-                    }),
-                    edit: Some(WorkspaceEdit {
-                        changes: Some(changes),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-                })
-            })
+            .map(|w| replace_action(format!("Replace with \"{}\"", w), uri, range, w))
             .collect::<Vec<_>>();
 
-        let title = format!("Add \"{word}\" to dictionary");
+        // Finding the occurrences checks the whole document
+        tokio::task::yield_now().await;
+        let occurrences = self.occurrences(uri, word);
+        if occurrences.len() > 1 {
+            code_actions.extend(suggestions.iter().map(|w| {
+                replace_all_action(
+                    format!("Replace all \"{word}\" with \"{w}\""),
+                    uri,
+                    &occurrences,
+                    w,
+                )
+            }));
+        }
+
+        if let Some(split) = self.split_suggestion(uri, &range, word) {
+            code_actions.push(replace_action(
+                format!("Split into \"{split}\""),
+                uri,
+                range,
+                &split,
+            ));
+        }
+
+        let title = format!("Add \"{word}\" to user dictionary");
         code_actions.push(CodeActionOrCommand::CodeAction(CodeAction {
             title: title.to_string(),
+            kind: Some(CodeActionKind::QUICKFIX),
             command: Some(Command {
                 title,
                 command: "add.to.dict".to_string(),
@@ -383,9 +1762,42 @@ impl LanguageServer for Backend {
             ..Default::default()
         }));
 
+        if folder.is_some() {
+            let title = format!("Add \"{word}\" to workspace dictionary");
+            code_actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: title.to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                command: Some(Command {
+                    title,
+                    command: "add.to.workspace.dict".to_string(),
+                    arguments: Some(vec![
+                        Value::String(word.to_string()),
+                        Value::String(uri.to_string()),
+                    ]),
+                }),
+                ..Default::default()
+            }));
+        }
+
+        let title = format!("Ignore \"{word}\" this session");
+        code_actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.to_string(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            command: Some(Command {
+                title,
+                command: "ignore.word.session".to_string(),
+                arguments: Some(vec![
+                    Value::String(word.to_string()),
+                    Value::String(uri.to_string()),
+                ]),
+            }),
+            ..Default::default()
+        }));
+
         let title = format!("Add all misspelled words in current file to local dictionary");
         code_actions.push(CodeActionOrCommand::CodeAction(CodeAction {
             title: title.to_string(),
+            kind: Some(CodeActionKind::QUICKFIX),
             command: Some(Command {
                 title,
                 command: "add.all.to.dict".to_string(),
@@ -393,40 +1805,2653 @@ impl LanguageServer for Backend {
             }),
             ..Default::default()
         }));
-
-        Ok(Some(code_actions))
+        code_actions
     }
 
-    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
-        match params.command.as_str() {
-            "add.to.dict" => self.add_to_dict(params).await,
-            "replace.with.word" => self.replace_with_word(params).await,
-            "add.all.to.dict" => self.add_all_to_dict(params).await,
-            _ => {}
+    /// Replaces every misspelled word of the document with its top
+    /// suggestion, words without suggestions are left alone
+    fn fix_all_action(&self, uri: &Url) -> Option<CodeActionOrCommand> {
+        let tokens = {
+            let source = self.sources.get(uri)?;
+            let folder = self.folder_of(uri);
+            self.misspelled_tokens(&source, &self.language_id(uri), folder.as_deref())
         };
-        return Ok(None);
+        let folder = self.folder_of(uri);
+        let mut top_suggestions = HashMap::<String, Option<String>>::new();
+        let mut edits = vec![];
+        // From the bottom up so clients applying the edits in order don't
+        // shift the ranges of the ones still to come
+        for token in tokens.into_iter().rev() {
+            let suggestion = top_suggestions
+                .entry(token.lexeme.to_lowercase())
+                .or_insert_with(|| {
+                    self.suggest(&token.lexeme, folder.as_deref())
+                        .into_iter()
+                        .next()
+                });
+            let Some(suggestion) = suggestion else {
+                continue;
+            };
+            edits.push(TextEdit {
+                range: Range::new(
+                    Position::new(token.start.line, token.start.col),
+                    Position::new(token.end.line, token.end.col),
+                ),
+                new_text: match_case(&token.lexeme, suggestion),
+            });
+        }
+        if edits.is_empty() {
+            return None;
+        }
+        Some(CodeActionOrCommand::CodeAction(CodeAction {
+            title: "Fix all spelling mistakes in file".to_string(),
+            kind: Some(FIX_ALL),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), edits)])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }))
+    }
+
+    /// When an operation started now times out, see `operation_timeout_ms`
+    fn operation_deadline(&self) -> Instant {
+        Instant::now() + Duration::from_millis(self.config.read().operation_timeout_ms)
+    }
+
+    fn recv_until<T>(
+        &self,
+        receiver: oneshot::Receiver<T>,
+        deadline: Instant,
+        word: &str,
+    ) -> Option<T> {
+        match receiver.recv_deadline(deadline) {
+            Ok(v) => Some(v),
+            Err(oneshot::RecvTimeoutError::Timeout) => {
+                warn!("Timed out while processing \"{word}\"");
+                None
+            }
+            Err(oneshot::RecvTimeoutError::Disconnected) => None,
+        }
     }
 }
 
-/// A fast, extensible code checker. Rustproof uses the Language Server Protocol (LSP) to communicate with your editor and detect spelling mistakes in your code. It handles a multitude of casings by breaking words into individual components.
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {}
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, init: InitializeParams) -> Result<InitializeResult> {
+        let folders = workspace_folders(&init);
+        *self.workspace_root.write() = folders.first().cloned();
+        *self.workspace_folders.write() = folders;
+        *self.pull_diagnostics.write() = init
+            .capabilities
+            .text_document
+            .as_ref()
+            .is_some_and(|t| t.diagnostic.is_some());
+        let position_encoding = PositionEncoding::negotiate(&init);
+        *self.position_encoding.write() = position_encoding;
+        *self.work_done_progress.write() = init
+            .capabilities
+            .window
+            .as_ref()
+            .and_then(|w| w.work_done_progress)
+            .unwrap_or(false);
+        self.load_config(init).await;
+        self.load_local_dict_from_file();
 
-#[tokio::main]
-async fn main() {
-    env_logger::init();
-    Args::parse();
-    let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
-    let (service, socket) = LspService::new(|client| Backend {
-        version: crate_version!(),
-        client,
-        local_dict: LocalDictionary::new(),
-        config: RwLock::new(Config::default()),
-        sources: DashMap::new(),
-        checker: RwLock::new(None),
-        suggester: RwLock::new(None),
-    });
+        Ok(InitializeResult {
+            server_info: Some(ServerInfo {
+                name: "Rustproof".to_string(),
+                version: Some(self.version.to_string()),
+            }),
+            offset_encoding: None,
+            capabilities: ServerCapabilities {
+                position_encoding: Some(position_encoding.kind()),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX, FIX_ALL]),
+                        work_done_progress_options: Default::default(),
+                        resolve_provider: None,
+                    },
+                )),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                    DiagnosticOptions {
+                        identifier: Some("rustproof".to_string()),
+                        inter_file_dependencies: false,
+                        workspace_diagnostics: false,
+                        work_done_progress_options: Default::default(),
+                    },
+                )),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
 
-    Server::new(stdin, stdout, socket).serve(service).await;
+                        ..Default::default()
+                    },
+                )),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        "replace.with.word".to_string(),
+                        "add.to.dict".to_string(),
+                        "add.to.workspace.dict".to_string(),
+                        "remove.from.dict".to_string(),
+                        "ignore.word.session".to_string(),
+                        "add.all.to.dict".to_string(),
+                        "add.all.confirm".to_string(),
+                        "list.misspelled".to_string(),
+                        "dict.export".to_string(),
+                        "dict.import.state".to_string(),
+                        "export.dict".to_string(),
+                        "import.dict".to_string(),
+                        "check.workspace".to_string(),
+                        "rustproof.status".to_string(),
+                        "update.dictionaries".to_string(),
+                        "clean.dict".to_string(),
+                    ],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        })
+    }
+
+    async fn initialized(&self, _: InitializedParams) {
+        self.watch_dict_files().await;
+        // Started once initialized since clients only accept progress from
+        // then on, documents opened before are checked again
+        self.start_spellchecker().await;
+        self.spell_check_all().await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let dict_files = self.dict_files();
+        let dict_changed = params
+            .changes
+            .iter()
+            .filter_map(|change| change.uri.to_file_path().ok())
+            .any(|path| dict_files.contains(&path));
+        if !dict_changed {
+            return;
+        }
+        info!("Reloading dictionaries");
+        self.reload_local_dicts();
+        self.spell_check_all().await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        info!("Reloading configuration");
+        let settings = match params.settings {
+            Value::Null => return,
+            // Clients usually namespace the settings by server
+            Value::Object(mut settings) if settings.contains_key("rustproof") => {
+                settings.remove("rustproof").unwrap()
+            }
+            settings => settings,
+        };
+        let previous = { self.config.read().clone() };
+        self.apply_settings(Some(settings)).await;
+
+        let (checker_changed, dict_paths_changed) = {
+            let config = self.config.read();
+            (
+                config.dictionaries != previous.dictionaries
+                    || config.checker_threads != previous.checker_threads
+                    || config.suggestion_engine != previous.suggestion_engine
+                    // The suggestion ranker is built with the spellchecker
+                    || config.suggestion_tiebreak != previous.suggestion_tiebreak
+                    || config.frequency_list != previous.frequency_list
+                    || config.proper_noun_suggestions != previous.proper_noun_suggestions,
+                config.dict_path != previous.dict_path
+                    || config.workspace_dict_path != previous.workspace_dict_path,
+            )
+        };
+        if dict_paths_changed {
+            self.reload_local_dicts();
+            self.unwatch_dict_files().await;
+            self.watch_dict_files().await;
+        }
+        if checker_changed {
+            self.start_spellchecker().await;
+        }
+        self.spell_check_all().await;
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        info!("shutdown");
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        info!("opened file");
+        let source = Rope::from(params.text_document.text);
+        let uri = params.text_document.uri;
+        self.sources.insert(uri.clone(), source);
+        self.languages
+            .insert(uri.clone(), params.text_document.language_id);
+        // Clients pulling diagnostics ask for them after opening and changing documents
+        if *self.pull_diagnostics.read() {
+            return;
+        }
+        self.spell_check_uri(uri).await;
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        info!("closed file");
+        let uri = params.text_document.uri;
+        self.sources.remove(&uri);
+        self.languages.remove(&uri);
+        self.typing_cursors.remove(&uri);
+        self.change_counts.remove(&uri);
+        self.has_directives.remove(&uri);
+        if let Some((_, timer)) = self.debounce_timers.remove(&uri) {
+            timer.abort();
+        }
+        let clear_on_close = { self.config.read().clear_on_close };
+        if clear_on_close {
+            // Otherwise the editor keeps showing the diagnostics of the closed file
+            self.publish_diagnostics(uri, vec![]).await;
+        } else {
+            self.diagnostics.remove(&uri);
+        }
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let previous = self.sources.get(&uri).map(|s| s.clone());
+        let mut source = previous.clone().unwrap_or_else(Rope::new);
+        let encoding = *self.position_encoding.read();
+        for change in &params.content_changes {
+            apply_content_change(&mut source, change, encoding);
+        }
+        self.sources.insert(uri.clone(), source);
+        *self.change_counts.entry(uri.clone()).or_default() += 1;
+        let suppress_while_typing = { self.config.read().suppress_while_typing };
+        let cursor = params
+            .content_changes
+            .last()
+            .and_then(|c| cursor_after(c, encoding));
+        match cursor {
+            Some(cursor) if suppress_while_typing => {
+                self.typing_cursors.insert(uri.clone(), cursor);
+            }
+            _ => {
+                self.typing_cursors.remove(&uri);
+            }
+        }
+        if *self.pull_diagnostics.read() {
+            return;
+        }
+        let check_mode = { self.config.read().check_mode };
+        let changes = params.content_changes.as_slice();
+        if let (CheckMode::Word, Some(previous), [change]) = (check_mode, previous, changes) {
+            if self.spell_check_edited_words(&uri, &previous, change).await {
+                return;
+            }
+        }
+        if self.debounce(&uri).await {
+            self.spell_check_uri(uri).await
+        }
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let only = params.context.only.as_deref();
+        let cursor_line = params.range.start.line;
+        let cursor_col = params.range.start.character;
+        let diagnostic_under_cursor = params.context.diagnostics.iter().find(|d| {
+            d.range.start.line == cursor_line
+                && d.range.start.character <= cursor_col
+                && cursor_col < d.range.end.character
+        });
+
+        let mut code_actions = vec![];
+        if let Some(diagnostic) = diagnostic_under_cursor {
+            if is_requested_kind(only, &CodeActionKind::QUICKFIX) {
+                code_actions.extend(self.quickfixes(&uri, diagnostic).await);
+            }
+        }
+        // Suggesting every word is too slow for each cursor move, fixing all
+        // is only offered when asked for, e.g. by a source action menu or on save
+        if only.is_some() && is_requested_kind(only, &FIX_ALL) {
+            tokio::task::yield_now().await;
+            code_actions.extend(self.fix_all_action(&uri));
+        }
+        Ok((!code_actions.is_empty()).then_some(code_actions))
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+        let diagnostics = {
+            match self.sources.get(&uri) {
+                Some(source) => self.document_diagnostics(&uri, &source),
+                None => vec![],
+            }
+        };
+        let items = self.store_diagnostics(&uri, diagnostics);
+        Ok(DocumentDiagnosticReportResult::Report(
+            DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id: None,
+                    items,
+                },
+            }),
+        ))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let position = params.text_document_position_params.position;
+        let uri = params.text_document_position_params.text_document.uri;
+        let misspelled = self.diagnostics.get(&uri).and_then(|diagnostics| {
+            diagnostics
+                .iter()
+                .find(|d| {
+                    d.range.start.line == position.line
+                        && d.range.start.character <= position.character
+                        && position.character < d.range.end.character
+                })
+                .map(|d| (d.range, d.data.clone()))
+        });
+        let Some((range, Some(Value::String(word)))) = misspelled else {
+            return Ok(None);
+        };
+
+        let suggestions = self.suggest(&word, self.folder_of(&uri).as_deref());
+        let value = if suggestions.is_empty() {
+            format!("No suggestions for \"{word}\"")
+        } else {
+            let list = suggestions
+                .iter()
+                .map(|s| format!("- {s}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("Suggestions for \"{word}\":\n\n{list}")
+        };
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range),
+        }))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        match params.command.as_str() {
+            "add.to.dict" => self.add_to_dict(params).await,
+            "add.to.workspace.dict" => self.add_to_workspace_dict(params).await,
+            "remove.from.dict" => self.remove_from_dict(params).await,
+            "ignore.word.session" => self.ignore_for_session(params).await,
+            "replace.with.word" => self.replace_with_word(params).await,
+            "add.all.to.dict" => return Ok(self.add_all_to_dict(params).await),
+            "add.all.confirm" => self.confirm_add_all(params).await,
+            "list.misspelled" => return Ok(self.list_misspelled(params)),
+            "dict.export" => return Ok(self.export_state().await),
+            "dict.import.state" => self.import_state(params).await,
+            "export.dict" => return Ok(self.export_dict(params).await),
+            "import.dict" => self.import_dict(params).await,
+            "check.workspace" => self.check_workspace().await,
+            "rustproof.status" => return Ok(self.status().await),
+            "update.dictionaries" => self.update_dictionaries().await,
+            "clean.dict" => self.clean_dict_files(),
+            _ => {}
+        };
+        return Ok(None);
+    }
+}
+
+/// The identifier of `text` the `token` is part of, e.g. `x7f3a9b2` for the
+/// token `x` or `fizz_buzz` for `buzz`. `text` starts at `text_start` of the
+/// document.
+fn identifier_of<'t>(text: &'t str, text_start: usize, token: &Token) -> &'t str {
+    let is_part = |c: &char| c.is_alphanumeric() || *c == '_';
+    let (start, end) = (token.start_byte - text_start, token.end_byte - text_start);
+    let before = text[..start]
+        .chars()
+        .rev()
+        .take_while(is_part)
+        .map(char::len_utf8)
+        .sum::<usize>();
+    let after = text[end..]
+        .chars()
+        .take_while(is_part)
+        .map(char::len_utf8)
+        .sum::<usize>();
+    &text[start - before..end + after]
+}
+
+fn is_acronym(lexeme: &str) -> bool {
+    lexeme.chars().count() > 1 && lexeme.chars().all(|c| c.is_uppercase())
+}
+
+/// The form a contraction or possessive is checked in when the dictionaries
+/// don't know it: `it's` becomes the stem `it` and `dont` gets its apostrophe
+/// back as `don't`. Only `n't` is restored, other suffixes like `'ll` or `'d`
+/// would turn misspellings such as `hell` or `shed` into valid words.
+fn contraction_variant(lexeme: &str) -> Option<String> {
+    let lowercase = lexeme.to_lowercase();
+    if lowercase.ends_with("'s") {
+        return Some(lexeme[..lexeme.len() - 2].to_string());
+    }
+    if lowercase.len() > 3 && lowercase.ends_with("nt") && !lowercase.contains('\'') {
+        let (stem, t) = lexeme.split_at(lexeme.len() - 1);
+        return Some(format!("{stem}'{t}"));
+    }
+    None
+}
+
+/// Replaces every token with `new_text`, keeping the casing of each token
+fn replace_all_action(
+    title: String,
+    uri: &Url,
+    tokens: &[Token],
+    new_text: &str,
+) -> CodeActionOrCommand {
+    let edits = tokens
+        .iter()
+        .map(|t| TextEdit {
+            range: Range::new(
+                Position::new(t.start.line, t.start.col),
+                Position::new(t.end.line, t.end.col),
+            ),
+            new_text: match_case(&t.lexeme, new_text),
+        })
+        .collect();
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Whether a client asking for `only` these kinds wants actions of `kind`,
+/// asking for `source` includes `source.fixAll`
+fn is_requested_kind(only: Option<&[CodeActionKind]>, kind: &CodeActionKind) -> bool {
+    let Some(only) = only else {
+        return true;
+    };
+    only.iter().any(|requested| {
+        let requested = requested.as_str();
+        kind.as_str() == requested || kind.as_str().starts_with(&format!("{requested}."))
+    })
+}
+
+fn replace_action(title: String, uri: &Url, range: Range, new_text: &str) -> CodeActionOrCommand {
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range,
+            new_text: new_text.to_string(),
+        }],
+    );
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        command: Some(Command {
+            title,
+            command: "replace.with.word".to_string(),
+            arguments: Some(vec![
+                Value::String(uri.to_string()),
+                serde_json::to_value(range).expect("Could not convert range to value"),
+                Value::String(new_text.to_string()),
+            ]),
+        }),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// One diagnostic per misspelled word at its first occurrence, the other
+/// occurrences are linked as related information
+fn grouped_by_word(uri: &Url, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut groups: Vec<(Diagnostic, Vec<Range>)> = vec![];
+    for diagnostic in diagnostics {
+        let group = groups.iter_mut().find(|(d, _)| d.data == diagnostic.data);
+        match group {
+            Some((_, ranges)) => ranges.push(diagnostic.range),
+            None => groups.push((diagnostic, vec![])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(mut diagnostic, ranges)| {
+            if ranges.is_empty() {
+                return diagnostic;
+            }
+            diagnostic.message =
+                format!("{} ({} occurrences)", diagnostic.message, ranges.len() + 1);
+            diagnostic.related_information = Some(
+                ranges
+                    .into_iter()
+                    .map(|range| DiagnosticRelatedInformation {
+                        location: tower_lsp::lsp_types::Location::new(uri.clone(), range),
+                        message: "Another occurrence".to_string(),
+                    })
+                    .collect(),
+            );
+            diagnostic
+        })
+        .collect()
+}
+
+/// Links every diagnostic to the other occurrences of its word so editors
+/// can jump between them
+fn with_related_occurrences(uri: &Url, mut diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut ranges: HashMap<String, Vec<Range>> = HashMap::new();
+    for diagnostic in &diagnostics {
+        // The truncation notice carries no word
+        if let Some(Value::String(word)) = &diagnostic.data {
+            ranges
+                .entry(word.to_owned())
+                .or_default()
+                .push(diagnostic.range);
+        }
+    }
+    for diagnostic in &mut diagnostics {
+        let Some(Value::String(word)) = &diagnostic.data else {
+            continue;
+        };
+        let others = ranges[word]
+            .iter()
+            .filter(|range| **range != diagnostic.range)
+            .take(MAX_RELATED_OCCURRENCES)
+            .map(|range| DiagnosticRelatedInformation {
+                location: tower_lsp::lsp_types::Location::new(uri.clone(), *range),
+                message: "Another occurrence".to_string(),
+            })
+            .collect::<Vec<_>>();
+        if !others.is_empty() {
+            diagnostic.related_information = Some(others);
+        }
+    }
+    diagnostics
+}
+
+fn load_dict_file(dict: &LocalDictionary, path: &Path) {
+    if !path.exists() {
+        return;
+    };
+    match dict.load_file(path) {
+        Ok(0) => {}
+        Ok(skipped) => warn!("Skipped {skipped} invalid UTF-8 lines in {path:?}"),
+        Err(e) => error!("Unable to read local dictionary {path:?}: {e}"),
+    }
+}
+
+/// Answers the requests on `threads` threads sharing the dictionaries, they
+/// stop once every sender is dropped
+fn serve_spell_requests(
+    threads: usize,
+    requests: mpsc::Receiver<SpellRequest>,
+    spellcheckers: Arc<Spellcheckers>,
+) {
+    let requests = Arc::new(Mutex::new(requests));
+    for _ in 0..threads.max(1) {
+        let requests = requests.clone();
+        let spellcheckers = spellcheckers.clone();
+        thread::spawn(move || loop {
+            // The lock is released before answering so the others can take
+            // the next request
+            let request = requests.lock().recv();
+            let Ok(request) = request else {
+                break;
+            };
+            spellcheckers.answer(request);
+        });
+    }
+}
+
+/// Cleans the user dictionary the first time it is loaded, words appended
+/// before `clean.dict` existed pile up duplicates. Workspace dictionaries are
+/// committed with the project and only cleaned on request.
+fn clean_dict_file_once(path: &Path) {
+    let marker = path.with_extension("cleaned");
+    if !path.exists() || marker.exists() {
+        return;
+    }
+    clean_dict_file(path);
+    if let Err(e) = fs::write(&marker, "") {
+        warn!("Unable to mark {path:?} as cleaned: {e}");
+    }
+}
+
+/// Rewrites the file at `path` sorted and without duplicates, the words keep
+/// their casing. Files that are clean already are left alone.
+fn clean_dict_file(path: &Path) {
+    // Rewriting the file would drop the lines that aren't valid UTF-8
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("Unable to clean dictionary {path:?}: {e}");
+            return;
+        }
+    };
+    let cleaned = cleaned_lines(&content)
+        .iter()
+        .map(|w| format!("{w}\n"))
+        .collect::<String>();
+    if cleaned == content {
+        return;
+    }
+    // Write to a temporary file first so a crash can't truncate the dictionary
+    let tmp = path.with_extension("tmp");
+    if let Err(e) = fs::write(&tmp, cleaned).and_then(|_| fs::rename(&tmp, path)) {
+        error!("Unable to clean dictionary {path:?}: {e}");
+    }
+}
+
+fn append_to_dict_file(path: &Path, word: &str) {
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Unable to create config dir");
+        }
+    };
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true) // Create if it doesn't exist
+        .open(path)
+        .expect("Unable to open local dictionary");
+
+    writeln!(file, "{word}").expect("Unable to append to local dictionary");
+}
+
+fn write_dict_file(path: &Path, words: &[String]) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).expect("Unable to create config dir");
+    }
+    let content = words.iter().map(|w| format!("{w}\n")).collect::<String>();
+    fs::write(path, content).expect("Unable to write local dictionary");
+}
+
+fn workspace_folders(init: &InitializeParams) -> Vec<PathBuf> {
+    match &init.workspace_folders {
+        Some(folders) if !folders.is_empty() => folders
+            .iter()
+            .filter_map(|f| f.uri.to_file_path().ok())
+            .collect(),
+        _ => init
+            .root_uri
+            .iter()
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect(),
+    }
+}
+
+/// A fast, extensible code checker. Rustproof uses the Language Server Protocol (LSP) to communicate with your editor and detect spelling mistakes in your code. It handles a multitude of casings by breaking words into individual components.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum CliCommand {
+    /// Spell checks files and directories without an editor, exits with a
+    /// non-zero status if there are misspellings
+    Check {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+    if let Some(CliCommand::Check { paths, format }) = args.command {
+        return cli::check(&paths, format).await;
+    }
+    let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
+    let (service, socket) = LspService::new(Backend::new);
+
+    Server::new(stdin, stdout, socket).serve(service).await;
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigDiagnosticSeverity, Dictionary};
+
+    fn test_service() -> LspService<Backend> {
+        let (service, _) = LspService::new(Backend::new);
+        service
+    }
+
+    /// Replaces hunspell with a checker that only knows `known_words`,
+    /// returns the words it was asked to check
+    fn start_fake_checker(
+        backend: &Backend,
+        known_words: &'static [&'static str],
+    ) -> Arc<Mutex<Vec<String>>> {
+        start_fake_spellchecker(backend, known_words, &[])
+    }
+
+    /// `start_fake_checker` that also suggests `suggestions` for every word
+    fn start_fake_spellchecker(
+        backend: &Backend,
+        known_words: &'static [&'static str],
+        suggestions: &'static [&'static str],
+    ) -> Arc<Mutex<Vec<String>>> {
+        let checked = Arc::new(Mutex::new(vec![]));
+        let (spellchecker, requests) = mpsc::channel::<SpellRequest>();
+        *backend.spellchecker.write() = Some(spellchecker);
+        backend.check_cache.clear();
+        backend.suggestion_cache.clear();
+        let checked_words = checked.clone();
+        thread::spawn(move || {
+            while let Ok(request) = requests.recv() {
+                match request {
+                    SpellRequest::Check((word, _, send)) => {
+                        let known = known_words.contains(&word.to_lowercase().as_str());
+                        checked_words.lock().push(word);
+                        let _ = send.send((known, vec![]));
+                    }
+                    SpellRequest::Suggest((_, send)) => {
+                        let _ = send.send(suggestions.iter().map(|s| s.to_string()).collect());
+                    }
+                }
+            }
+        });
+        checked
+    }
+
+    fn temp_dict_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rustproof-{name}-dict.txt"));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn open_document(backend: &Backend, text: &str) -> Url {
+        let uri = Url::parse("file:///project/document.txt").unwrap();
+        backend.sources.insert(uri.clone(), Rope::from(text));
+        uri
+    }
+
+    fn command(command: &str, arguments: Vec<Value>) -> ExecuteCommandParams {
+        ExecuteCommandParams {
+            command: command.to_string(),
+            arguments,
+            work_done_progress_params: Default::default(),
+        }
+    }
+
+    fn change(uri: &Url, text: &str) -> DidChangeTextDocumentParams {
+        DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 1),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        }
+    }
+
+    fn published_words(backend: &Backend, uri: &Url) -> Vec<(String, u32, u32)> {
+        backend
+            .diagnostics
+            .get(uri)
+            .unwrap()
+            .iter()
+            .map(|d| {
+                let Some(Value::String(word)) = &d.data else {
+                    panic!("Diagnostic is missing the word");
+                };
+                (word.clone(), d.range.start.line, d.range.start.character)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn removes_words_from_the_local_dictionary() {
+        let service = test_service();
+        let backend = service.inner();
+        let dict_path = temp_dict_path("remove-from-dict");
+        backend.config.write().dict_path = dict_path.clone();
+        start_fake_checker(backend, &[]);
+        backend.insert_into_local_dict("wrold");
+        backend.insert_into_local_dict("Kubernetes");
+        let uri = open_document(backend, "wrold kubernetes");
+
+        backend
+            .execute_command(command(
+                "remove.from.dict",
+                vec![
+                    Value::String("kubernetes".to_string()),
+                    Value::String(uri.to_string()),
+                ],
+            ))
+            .await
+            .unwrap();
+
+        assert!(!backend.local_dict.contains("kubernetes"));
+        assert!(backend.local_dict.contains("wrold"));
+        assert_eq!(fs::read_to_string(&dict_path).unwrap(), "wrold\n");
+        assert_eq!(backend.diagnostics.get(&uri).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cleans_the_dictionary_file() {
+        let service = test_service();
+        let backend = service.inner();
+        let dict_path = temp_dict_path("clean-dict");
+        let _ = fs::remove_file(dict_path.with_extension("cleaned"));
+        let folder = std::env::temp_dir().join("rustproof-clean-dict-folder");
+        let _ = fs::remove_dir_all(&folder);
+        *backend.workspace_folders.write() = vec![folder.clone()];
+        backend.config.write().dict_path = dict_path.clone();
+        fs::write(&dict_path, "wrold\nKubernetes\n\nWrold\n").unwrap();
+        let workspace_dict_path = backend.workspace_dict_path(&folder);
+        fs::create_dir_all(workspace_dict_path.parent().unwrap()).unwrap();
+        fs::write(&workspace_dict_path, "kubectl\nHelm\nkubectl\n").unwrap();
+
+        // The user dictionary is cleaned once, committed workspace ones never
+        backend.load_local_dict_from_file();
+        assert_eq!(
+            fs::read_to_string(&dict_path).unwrap(),
+            "Kubernetes\nwrold\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&workspace_dict_path).unwrap(),
+            "kubectl\nHelm\nkubectl\n"
+        );
+        backend.insert_into_local_dict("kubernetes");
+        backend.reload_local_dicts();
+        assert_eq!(
+            fs::read_to_string(&dict_path).unwrap(),
+            "Kubernetes\nwrold\nkubernetes\n"
+        );
+
+        backend
+            .execute_command(command("clean.dict", vec![]))
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&dict_path).unwrap(),
+            "Kubernetes\nwrold\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&workspace_dict_path).unwrap(),
+            "Helm\nkubectl\n"
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reloads_the_dictionary_when_its_file_changes() {
+        let service = test_service();
+        let backend = service.inner();
+        let dict_path = temp_dict_path("watched");
+        backend.config.write().dict_path = dict_path.clone();
+        start_fake_checker(backend, &[]);
+        backend.insert_into_local_dict("wrold");
+        let uri = open_document(backend, "wrold kubernetes");
+
+        fs::write(&dict_path, "kubernetes\n").unwrap();
+        backend
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent::new(
+                    Url::from_file_path(&dict_path).unwrap(),
+                    FileChangeType::CHANGED,
+                )],
+            })
+            .await;
+
+        assert!(backend.local_dict.contains("kubernetes"));
+        assert!(!backend.local_dict.contains("wrold"));
+        assert_eq!(backend.diagnostics.get(&uri).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn adds_words_to_the_dictionary_of_the_workspace_folder() {
+        let service = test_service();
+        let backend = service.inner();
+        let root = std::env::temp_dir().join("rustproof-workspace-dict");
+        let _ = fs::remove_dir_all(&root);
+        let (app, lib) = (root.join("app"), root.join("lib"));
+        *backend.workspace_root.write() = Some(app.clone());
+        *backend.workspace_folders.write() = vec![app.clone(), lib.clone()];
+        backend.config.write().dict_path = temp_dict_path("workspace-dict-user");
+        start_fake_checker(backend, &[]);
+        let open = |path: PathBuf| {
+            let uri = Url::from_file_path(path).unwrap();
+            backend
+                .sources
+                .insert(uri.clone(), Rope::from("wrold kubernetes"));
+            uri
+        };
+        let app_uri = open(app.join("main.txt"));
+        let lib_uri = open(lib.join("main.txt"));
+
+        let titles = code_action_titles(backend, &app_uri).await;
+        assert!(titles.contains(&"Add \"wrold\" to user dictionary".to_string()));
+        assert!(titles.contains(&"Add \"wrold\" to workspace dictionary".to_string()));
+        let titles = code_action_titles(backend, &open_document(backend, "wrold")).await;
+        assert!(!titles.contains(&"Add \"wrold\" to workspace dictionary".to_string()));
+
+        backend
+            .execute_command(command(
+                "add.to.workspace.dict",
+                vec![
+                    Value::String("kubernetes".to_string()),
+                    Value::String(app_uri.to_string()),
+                ],
+            ))
+            .await
+            .unwrap();
+        backend.spell_check_uri(lib_uri.clone()).await;
+
+        assert!(backend.in_local_dicts("kubernetes", Some(&app)));
+        assert!(!backend.in_local_dicts("kubernetes", Some(&lib)));
+        assert!(!backend.local_dict.contains("kubernetes"));
+        assert_eq!(
+            fs::read_to_string(app.join(".rustproof/words.txt")).unwrap(),
+            "kubernetes\n"
+        );
+        assert_eq!(backend.diagnostics.get(&app_uri).unwrap().len(), 1);
+        assert_eq!(backend.diagnostics.get(&lib_uri).unwrap().len(), 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_workspace_skips_gitignored_files() {
+        let service = test_service();
+        let backend = service.inner();
+        let root = std::env::temp_dir().join("rustproof-check-workspace");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join("notes.md"), "helo wrold").unwrap();
+        fs::write(root.join("debug.log"), "wrold").unwrap();
+        *backend.workspace_root.write() = Some(root.clone());
+        start_fake_checker(backend, &[]);
+
+        backend
+            .execute_command(command("check.workspace", vec![]))
+            .await
+            .unwrap();
+
+        let notes = Url::from_file_path(root.join("notes.md")).unwrap();
+        let log = Url::from_file_path(root.join("debug.log")).unwrap();
+        assert_eq!(backend.diagnostics.get(&notes).unwrap().len(), 2);
+        assert!(backend.diagnostics.get(&log).is_none());
+
+        backend.config.write().respect_gitignore = false;
+        backend
+            .execute_command(command("check.workspace", vec![]))
+            .await
+            .unwrap();
+        assert_eq!(backend.diagnostics.get(&log).unwrap().len(), 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn project_config_is_layered_over_the_user_config() {
+        let service = test_service();
+        let backend = service.inner();
+        let root = std::env::temp_dir().join("rustproof-project-config");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join(PROJECT_CONFIG_FILES[0]),
+            "diagnostic_severity = \"hint\"\nignore_regex = [\"x[0-9]+\"]\n",
+        )
+        .unwrap();
+        *backend.workspace_root.write() = Some(root.clone());
+
+        backend
+            .load_config(InitializeParams {
+                initialization_options: Some(serde_json::json!({
+                    "diagnostic_severity": "warning",
+                    "ignore_regex": ["[0-9a-f]{40}"],
+                    "min_word_length": 3
+                })),
+                ..Default::default()
+            })
+            .await;
+        let status = backend
+            .execute_command(command("rustproof.status", vec![]))
+            .await
+            .unwrap()
+            .unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let config = &status["config"];
+        assert_eq!(config["diagnostic_severity"], "hint");
+        assert_eq!(
+            config["ignore_regex"],
+            serde_json::json!(["[0-9a-f]{40}", "x[0-9]+"])
+        );
+        assert_eq!(config["min_word_length"], 3);
+        assert_eq!(backend.ignore_regexes.read().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reads_the_words_of_a_cspell_config() {
+        let service = test_service();
+        let backend = service.inner();
+        let root = std::env::temp_dir().join("rustproof-cspell-config");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("cspell.json"),
+            r#"{"words": ["kubernetes"], "flagWords": ["masterfile"]}"#,
+        )
+        .unwrap();
+        *backend.workspace_root.write() = Some(root.clone());
+
+        backend
+            .load_config(InitializeParams {
+                initialization_options: Some(serde_json::json!({ "words": ["kustomize"] })),
+                ..Default::default()
+            })
+            .await;
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(backend.config.read().words, vec!["kubernetes", "kustomize"]);
+        assert!(backend.config_words.contains("Kubernetes"));
+        assert!(backend.flagged_words.read().contains_key("masterfile"));
+    }
+
+    #[tokio::test]
+    async fn reads_a_json_project_config() {
+        let service = test_service();
+        let backend = service.inner();
+        let root = std::env::temp_dir().join("rustproof-json-project-config");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".rustproof.json"), r#"{"min_word_length": 6}"#).unwrap();
+        *backend.workspace_root.write() = Some(root.clone());
+
+        backend.load_config(InitializeParams::default()).await;
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(backend.config.read().min_word_length, 6);
+    }
+
+    #[tokio::test]
+    async fn lists_misspelled_words_with_their_counts() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["hello"]);
+        let uri = open_document(backend, "hello wrold, helo wrold");
+
+        let result = backend
+            .execute_command(command(
+                "list.misspelled",
+                vec![Value::String(uri.to_string())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            Some(serde_json::json!([
+                { "word": "helo", "count": 1 },
+                { "word": "wrold", "count": 2 }
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn add_all_returns_words_for_confirmation() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().dict_path = temp_dict_path("add-all-confirm");
+        start_fake_checker(backend, &["hello"]);
+        let uri = open_document(backend, "hello wrold, helo wrold");
+
+        let result = backend
+            .execute_command(command(
+                "add.all.to.dict",
+                vec![Value::String(uri.to_string())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(result, Some(serde_json::json!(["helo", "wrold"])));
+        assert!(!backend.local_dict.contains("wrold"));
+
+        let result = backend
+            .execute_command(command(
+                "add.all.confirm",
+                vec![Value::String(uri.to_string()), serde_json::json!(["wrold"])],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(result, None);
+        assert!(backend.local_dict.contains("wrold"));
+        assert!(!backend.local_dict.contains("helo"));
+    }
+
+    #[tokio::test]
+    async fn add_all_adds_immediately_when_configured() {
+        let service = test_service();
+        let backend = service.inner();
+        {
+            let mut config = backend.config.write();
+            config.dict_path = temp_dict_path("add-all-immediately");
+            config.add_all_immediately = true;
+        }
+        start_fake_checker(backend, &["hello"]);
+        let uri = open_document(backend, "hello wrold, helo wrold");
+
+        let result = backend
+            .execute_command(command(
+                "add.all.to.dict",
+                vec![Value::String(uri.to_string())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(result, None);
+        assert!(backend.local_dict.contains("wrold"));
+        assert!(backend.local_dict.contains("helo"));
+    }
+
+    #[tokio::test]
+    async fn word_mode_only_rechecks_the_edited_word() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().check_mode = CheckMode::Word;
+        let checked = start_fake_checker(backend, &["hello", "world", "another", "line"]);
+        let uri = open_document(backend, "helo wrold\nanother lnie");
+        backend.spell_check_uri(uri.clone()).await;
+        assert_eq!(
+            published_words(backend, &uri),
+            vec![
+                ("helo".to_string(), 0, 0),
+                ("wrold".to_string(), 0, 5),
+                ("lnie".to_string(), 1, 8)
+            ]
+        );
+        checked.lock().clear();
+
+        backend.did_change(type_text(&uri, 0, 3, "l")).await;
+
+        assert_eq!(*checked.lock(), vec!["hello"]);
+        assert_eq!(
+            published_words(backend, &uri),
+            vec![("wrold".to_string(), 0, 6), ("lnie".to_string(), 1, 8)]
+        );
+    }
+
+    #[tokio::test]
+    async fn word_mode_skips_edited_words_inside_urls() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().check_mode = CheckMode::Word;
+        start_fake_checker(backend, &["visit", "today"]);
+        let uri = open_document(backend, "visit https://exampel.com today");
+        backend.spell_check_uri(uri.clone()).await;
+        assert!(published_words(backend, &uri).is_empty());
+
+        backend.did_change(type_text(&uri, 0, 20, "l")).await;
+
+        assert!(published_words(backend, &uri).is_empty());
+    }
+
+    #[tokio::test]
+    async fn word_mode_skips_ordinal_suffixes_like_full_checks() {
+        let service = test_service();
+        let backend = service.inner();
+        {
+            let mut config = backend.config.write();
+            config.check_mode = CheckMode::Word;
+            config.min_word_length = 2;
+        }
+        let checked = start_fake_checker(backend, &["meet", "on", "the", "today"]);
+        let uri = open_document(backend, "meet on the 2 today");
+        backend.spell_check_uri(uri.clone()).await;
+        checked.lock().clear();
+
+        backend.did_change(type_text(&uri, 0, 13, "1st")).await;
+
+        assert!(checked.lock().is_empty());
+        assert!(published_words(backend, &uri).is_empty());
+    }
+
+    #[tokio::test]
+    async fn word_mode_checks_the_whole_document_around_directives() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().check_mode = CheckMode::Word;
+        start_fake_checker(backend, &[]);
+        let uri = open_document(backend, "helo\nwrold");
+        backend.spell_check_uri(uri.clone()).await;
+
+        backend
+            .did_change(type_text(&uri, 0, 4, " // rustproof-disable-line"))
+            .await;
+
+        assert_eq!(
+            published_words(backend, &uri),
+            vec![("wrold".to_string(), 1, 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_checks_the_last_of_rapid_changes() {
+        let service = test_service();
+        let backend = service.inner();
+        let checked = start_fake_checker(backend, &[]);
+        let uri = open_document(backend, "");
+
+        tokio::join!(
+            backend.did_change(change(&uri, "helo")),
+            backend.did_change(change(&uri, "helo wrold")),
+        );
+
+        assert_eq!(*checked.lock(), vec!["helo", "wrold"]);
+        assert_eq!(
+            published_words(backend, &uri),
+            vec![("helo".to_string(), 0, 0), ("wrold".to_string(), 0, 5)]
+        );
+    }
+
+    #[tokio::test]
+    async fn abandons_a_check_when_the_document_changes() {
+        let service = test_service();
+        let backend = service.inner();
+        let checked = start_fake_checker(backend, &[]);
+        let words = (0..2 * CHECK_CHUNK_WORDS)
+            .map(|i| {
+                let letter = |n: usize| char::from(b'a' + (n % 26) as u8);
+                format!("wo{}{}", letter(i / 26), letter(i))
+            })
+            .collect::<Vec<_>>();
+        let uri = open_document(backend, &words.join(" "));
+
+        tokio::join!(
+            backend.spell_check_uri(uri.clone()),
+            backend.did_change(change(&uri, "helo")),
+        );
+
+        // The first chunk was checked before the change was handled
+        let checked = checked.lock();
+        assert_eq!(checked.len(), CHECK_CHUNK_WORDS + 1);
+        assert_eq!(checked.last().unwrap(), "helo");
+        assert_eq!(
+            published_words(backend, &uri),
+            vec![("helo".to_string(), 0, 0)]
+        );
+    }
+
+    #[test]
+    fn subtle_presentation_reports_unnecessary_hints() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().diagnostic_presentation = DiagnosticPresentation::Subtle;
+        start_fake_checker(backend, &[]);
+
+        let diagnostics = backend.spell_check_code(&Rope::from("wrold"), "", None);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diagnostics[0].tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+    }
+
+    #[test]
+    fn underline_presentation_uses_the_configured_severity() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+
+        let diagnostics = backend.spell_check_code(&Rope::from("wrold"), "", None);
+
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[0].tags, None);
+    }
+
+    #[tokio::test]
+    async fn did_close_clears_diagnostics_when_configured() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let uri = open_document(backend, "helo wrold");
+        backend.spell_check_uri(uri.clone()).await;
+        assert_eq!(backend.diagnostics.get(&uri).unwrap().len(), 2);
+
+        backend
+            .did_close(DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+            })
+            .await;
+
+        assert!(backend.diagnostics.get(&uri).unwrap().is_empty());
+        assert!(backend.sources.get(&uri).is_none());
+    }
+
+    #[tokio::test]
+    async fn did_close_keeps_diagnostics_in_the_editor_when_disabled() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().clear_on_close = false;
+        start_fake_checker(backend, &[]);
+        let uri = open_document(backend, "helo wrold");
+        backend.spell_check_uri(uri.clone()).await;
+
+        backend
+            .did_close(DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+            })
+            .await;
+
+        // Nothing was published, the map only tracks open documents
+        assert!(backend.diagnostics.get(&uri).is_none());
+    }
+
+    #[test]
+    fn suggests_splitting_run_together_words_in_prose() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["the", "quick", "brown", "let"]);
+        let uri = open_document(backend, "let thequickbrown = 1; // thequickbrown");
+        let code = Range::new(Position::new(0, 4), Position::new(0, 17));
+        let comment = Range::new(Position::new(0, 26), Position::new(0, 39));
+
+        assert_eq!(
+            backend.split_suggestion(&uri, &code, "thequickbrown"),
+            Some("the quick brown".to_string())
+        );
+
+        backend.languages.insert(uri.clone(), "rust".to_string());
+        assert_eq!(backend.split_suggestion(&uri, &code, "thequickbrown"), None);
+        assert_eq!(
+            backend.split_suggestion(&uri, &comment, "thequickbrown"),
+            Some("the quick brown".to_string())
+        );
+        assert_eq!(
+            backend.split_suggestion(&uri, &comment, "thequikbrown"),
+            None
+        );
+    }
+
+    #[test]
+    fn replaces_words_after_multibyte_characters() {
+        let service = test_service();
+        let backend = service.inner();
+        let uri = open_document(
+            backend,
+            "🤖 café wrold
+next",
+        );
+        let range = Range::new(Position::new(0, 8), Position::new(0, 13));
+
+        assert!(backend.replace_word_in_source(&uri, &range, "world"));
+
+        assert_eq!(
+            backend.sources.get(&uri).unwrap().to_string(),
+            "🤖 café world\nnext"
+        );
+    }
+
+    #[test]
+    fn replaces_the_original_span_when_lengths_differ() {
+        let service = test_service();
+        let backend = service.inner();
+        let uri = open_document(backend, "naïve wrld here");
+        let range = Range::new(Position::new(0, 6), Position::new(0, 10));
+
+        assert!(backend.replace_word_in_source(&uri, &range, "world"));
+
+        assert_eq!(
+            backend.sources.get(&uri).unwrap().to_string(),
+            "naïve world here"
+        );
+    }
+
+    #[test]
+    fn keeps_the_casing_of_replaced_words() {
+        let service = test_service();
+        let backend = service.inner();
+        let uri = open_document(backend, "recieveData DataRecieve RECIEVE");
+
+        let replace = |start, end| {
+            let range = Range::new(Position::new(0, start), Position::new(0, end));
+            assert!(backend.replace_word_in_source(&uri, &range, "receive"));
+        };
+        replace(24, 31);
+        replace(16, 23);
+        replace(0, 7);
+
+        assert_eq!(
+            backend.sources.get(&uri).unwrap().to_string(),
+            "receiveData DataReceive RECEIVE"
+        );
+    }
+
+    #[test]
+    fn checks_short_acronyms_when_configured() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["open", "the", "io", "fine"]);
+        let code = Rope::from("open the DBB and ioDB, IO is fine");
+
+        assert!(backend.misspelled_tokens(&code, "", None).is_empty());
+
+        backend.config.write().check_short_acronyms = true;
+        let tokens = backend.misspelled_tokens(&code, "", None);
+        let words = tokens.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>();
+        assert_eq!(words, vec!["DBB", "DB"]);
+    }
+
+    #[test]
+    fn consults_the_dictionary_group_of_the_language() {
+        let service = test_service();
+        let backend = service.inner();
+        {
+            let mut config = backend.config.write();
+            config.dictionary_groups = HashMap::from([(
+                "web".to_string(),
+                vec!["en".to_string(), "html".to_string()],
+            )]);
+            config.language_groups = HashMap::from([("html".to_string(), "web".to_string())]);
+        }
+        let consulted = Arc::new(Mutex::new(vec![]));
+        let (spellchecker, requests) = mpsc::channel::<SpellRequest>();
+        *backend.spellchecker.write() = Some(spellchecker);
+        let consulted_dictionaries = consulted.clone();
+        thread::spawn(move || {
+            while let Ok(SpellRequest::Check((_, dictionaries, send))) = requests.recv() {
+                consulted_dictionaries.lock().push(dictionaries);
+                let _ = send.send((true, vec![]));
+            }
+        });
+        let code = Rope::from("hello");
+
+        backend.misspelled_tokens(&code, "html", None);
+        backend.misspelled_tokens(&code, "rust", None);
+
+        assert_eq!(
+            *consulted.lock(),
+            vec![Some(vec!["en".to_string(), "html".to_string()]), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn did_change_applies_incremental_changes() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["hello", "world"]);
+        let uri = open_document(backend, "🤖 helo wrold");
+
+        backend
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 2,
+                },
+                content_changes: vec![
+                    TextDocumentContentChangeEvent {
+                        range: Some(Range::new(Position::new(0, 5), Position::new(0, 5))),
+                        range_length: None,
+                        text: "l".to_string(),
+                    },
+                    TextDocumentContentChangeEvent {
+                        range: Some(Range::new(Position::new(0, 9), Position::new(0, 14))),
+                        range_length: None,
+                        text: "world".to_string(),
+                    },
+                ],
+            })
+            .await;
+
+        assert_eq!(
+            backend.sources.get(&uri).unwrap().to_string(),
+            "🤖 hello world"
+        );
+        assert!(backend.diagnostics.get(&uri).unwrap().is_empty());
+    }
+
+    #[test]
+    fn checks_words_down_to_the_min_word_length() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["the"]);
+        let code = Rope::from("teh cat");
+
+        assert!(backend.misspelled_tokens(&code, "", None).is_empty());
+
+        backend.config.write().min_word_length = 3;
+        let tokens = backend.misspelled_tokens(&code, "", None);
+        let words = tokens.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>();
+        assert_eq!(words, vec!["teh", "cat"]);
+    }
+
+    fn type_text(uri: &Url, line: u32, col: u32, text: &str) -> DidChangeTextDocumentParams {
+        DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: 2,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(
+                    Position::new(line, col),
+                    Position::new(line, col),
+                )),
+                range_length: None,
+                text: text.to_string(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn suppresses_the_word_being_typed_until_a_boundary() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().suppress_while_typing = true;
+        start_fake_checker(backend, &["hello"]);
+        let uri = open_document(backend, "hello wor");
+        let diagnostics = || {
+            let published = backend.diagnostics.get(&uri).unwrap().clone();
+            backend.without_typed_word(&uri, published)
+        };
+
+        backend.did_change(type_text(&uri, 0, 9, "l")).await;
+        assert!(diagnostics().is_empty());
+        assert_eq!(backend.diagnostics.get(&uri).unwrap().len(), 1);
+
+        backend.did_change(type_text(&uri, 0, 10, " ")).await;
+        assert_eq!(diagnostics().len(), 1);
+        assert_eq!(diagnostics()[0].range.start, Position::new(0, 6));
+    }
+
+    async fn code_action_titles(backend: &Backend, uri: &Url) -> Vec<String> {
+        code_actions(backend, uri)
+            .await
+            .into_iter()
+            .map(|a| match a {
+                CodeActionOrCommand::CodeAction(a) => a.title,
+                CodeActionOrCommand::Command(c) => c.title,
+            })
+            .collect()
+    }
+
+    /// Code actions for a misspelled "wrold" at the start of the document
+    async fn code_actions(backend: &Backend, uri: &Url) -> Vec<CodeActionOrCommand> {
+        code_actions_of_kinds(backend, uri, None).await
+    }
+
+    async fn code_actions_of_kinds(
+        backend: &Backend,
+        uri: &Url,
+        only: Option<Vec<CodeActionKind>>,
+    ) -> Vec<CodeActionOrCommand> {
+        let range = Range::new(Position::new(0, 0), Position::new(0, 5));
+        let diagnostic = Diagnostic {
+            range,
+            data: Some(Value::String("wrold".to_string())),
+            ..Default::default()
+        };
+        backend
+            .code_action(CodeActionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                range,
+                context: CodeActionContext {
+                    diagnostics: vec![diagnostic],
+                    only,
+                    ..Default::default()
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn suggests_local_dictionary_words_for_close_misspellings() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_spellchecker(backend, &[], &["Kubernetes", "kibbutzes"]);
+        backend.local_dict.insert("kubernetes".to_string());
+        backend.local_dict.insert("kustomize".to_string());
+
+        assert_eq!(
+            backend.suggest("kubernets", None),
+            vec!["kubernetes", "Kubernetes", "kibbutzes"]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_flagged_words_known_to_the_dictionaries() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_spellchecker(backend, &["masterfile", "tehh", "hello"], &["tech"]);
+        backend
+            .apply_settings(Some(serde_json::json!({
+                "flag_words": ["masterfile", "tehh->then"]
+            })))
+            .await;
+
+        let code = Rope::from("hello masterfile tehh");
+        let messages = backend
+            .spell_check_code(&code, "", None)
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Flagged word \"masterfile\"",
+                "Flagged word \"tehh\", use \"then\""
+            ]
+        );
+        assert_eq!(backend.suggest("tehh", None), vec!["then", "tech"]);
+    }
+
+    #[tokio::test]
+    async fn accepts_the_words_of_the_config() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().dict_path = temp_dict_path("config-words");
+        start_fake_checker(backend, &[]);
+        backend
+            .apply_settings(Some(serde_json::json!({ "words": ["Kubernetes"] })))
+            .await;
+
+        let code = Rope::from("kubernetes wrold");
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(0, 11));
+        // Config words aren't written to the dictionary file
+        assert!(!backend.local_dict.contains("kubernetes"));
+    }
+
+    #[tokio::test]
+    async fn hover_lists_suggestions_for_misspelled_words() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_spellchecker(backend, &["hello"], &["world", "would"]);
+        let uri = open_document(backend, "hello wrold");
+        backend.spell_check_uri(uri.clone()).await;
+
+        let hover = |character| HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri.clone()),
+                Position::new(0, character),
+            ),
+            work_done_progress_params: Default::default(),
+        };
+
+        let Some(Hover {
+            contents: HoverContents::Markup(markup),
+            range,
+        }) = backend.hover(hover(8)).await.unwrap()
+        else {
+            panic!("expected a markdown hover");
+        };
+        assert_eq!(
+            markup.value,
+            "Suggestions for \"wrold\":\n\n- world\n- would"
+        );
+        assert_eq!(
+            range,
+            Some(Range::new(Position::new(0, 6), Position::new(0, 11)))
+        );
+
+        assert_eq!(backend.hover(hover(2)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn pulled_diagnostics_are_computed_on_request() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["hello"]);
+        let uri = open_document(backend, "hello wrold");
+
+        let report = backend
+            .diagnostic(DocumentDiagnosticParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                identifier: None,
+                previous_result_id: None,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap();
+
+        let DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) = report
+        else {
+            panic!("expected a full report");
+        };
+        let items = report.full_document_diagnostic_report.items;
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].range,
+            Range::new(Position::new(0, 6), Position::new(0, 11))
+        );
+        // Code actions and hovers use the stored diagnostics
+        assert_eq!(backend.diagnostics.get(&uri).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn replaces_all_occurrences_of_a_misspelling() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_spellchecker(backend, &[], &["world"]);
+        let uri = open_document(backend, "wrold and\nWrold");
+
+        let edits = code_actions(backend, &uri)
+            .await
+            .into_iter()
+            .find_map(|a| match a {
+                CodeActionOrCommand::CodeAction(a)
+                    if a.title == "Replace all \"wrold\" with \"world\"" =>
+                {
+                    a.edit?.changes?.remove(&uri)
+                }
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(
+            edits,
+            vec![
+                TextEdit {
+                    range: Range::new(Position::new(0, 0), Position::new(0, 5)),
+                    new_text: "world".to_string(),
+                },
+                TextEdit {
+                    range: Range::new(Position::new(1, 0), Position::new(1, 5)),
+                    new_text: "World".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_words_for_the_session() {
+        let service = test_service();
+        let backend = service.inner();
+        let dict_path = temp_dict_path("session-words");
+        backend.config.write().dict_path = dict_path.clone();
+        start_fake_checker(backend, &[]);
+        let uri = open_document(backend, "wrold");
+        let titles = code_action_titles(backend, &uri).await;
+        assert!(titles.contains(&"Ignore \"wrold\" this session".to_string()));
+
+        backend
+            .execute_command(command(
+                "ignore.word.session",
+                vec![Value::from("wrold"), Value::String(uri.to_string())],
+            ))
+            .await
+            .unwrap();
+
+        assert!(backend.diagnostics.get(&uri).unwrap().is_empty());
+        assert!(!backend.local_dict.contains("wrold"));
+        assert!(!dict_path.exists());
+    }
+
+    #[tokio::test]
+    async fn limits_the_number_of_suggestions() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_spellchecker(backend, &[], &["world", "would", "wold"]);
+        let uri = open_document(backend, "wrold");
+
+        backend.config.write().max_suggestions = 2;
+        let titles = code_action_titles(backend, &uri).await;
+        assert_eq!(
+            titles[..2],
+            ["Replace with \"world\"", "Replace with \"would\""]
+        );
+        assert_eq!(titles[2], "Add \"wrold\" to user dictionary");
+
+        backend.config.write().max_suggestions = 0;
+        let titles = code_action_titles(backend, &uri).await;
+        assert!(titles.iter().all(|t| !t.starts_with("Replace with")));
+        assert_eq!(titles[0], "Add \"wrold\" to user dictionary");
+
+        backend.config.write().max_suggestions = usize::MAX;
+        assert_eq!(code_action_titles(backend, &uri).await.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn marks_code_actions_as_quickfixes() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_spellchecker(backend, &[], &["world"]);
+        let uri = open_document(backend, "wrold");
+
+        let actions = code_actions(backend, &uri).await;
+        assert!(!actions.is_empty());
+        assert!(actions.iter().all(|a| matches!(
+            a,
+            CodeActionOrCommand::CodeAction(CodeAction {
+                kind: Some(kind),
+                ..
+            }) if *kind == CodeActionKind::QUICKFIX
+        )));
+
+        let only = Some(vec![CodeActionKind::QUICKFIX]);
+        assert_eq!(
+            code_actions_of_kinds(backend, &uri, only).await.len(),
+            actions.len()
+        );
+        let only = Some(vec![CodeActionKind::REFACTOR]);
+        assert!(code_actions_of_kinds(backend, &uri, only).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fixes_all_misspellings_with_their_top_suggestion() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_spellchecker(backend, &[], &[]);
+        backend.local_dict.insert("world".to_string());
+        let uri = open_document(backend, "wrold helo\nWrold");
+
+        assert!(code_actions(backend, &uri).await.iter().all(|a| matches!(
+            a,
+            CodeActionOrCommand::CodeAction(CodeAction { kind: Some(kind), .. }) if *kind != FIX_ALL
+        )));
+        let only = Some(vec![CodeActionKind::SOURCE]);
+        let actions = code_actions_of_kinds(backend, &uri, only).await;
+        let [CodeActionOrCommand::CodeAction(action)] = actions.as_slice() else {
+            panic!("Expected a single fix all action, got {actions:?}");
+        };
+        assert_eq!(action.kind, Some(FIX_ALL));
+        let edits = &action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+        // "helo" has no suggestion and is left alone
+        assert_eq!(
+            edits
+                .iter()
+                .map(|e| (e.range.start, e.new_text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Position::new(1, 0), "World"),
+                (Position::new(0, 0), "world")
+            ]
+        );
+    }
+
+    #[test]
+    fn severity_depends_on_the_rejecting_dictionaries() {
+        let service = test_service();
+        let backend = service.inner();
+        {
+            let mut config = backend.config.write();
+            let mut code = Dictionary::new("en-code", "", "");
+            code.severity = Some(ConfigDiagnosticSeverity::Hint);
+            let mut prose = Dictionary::new("en", "", "");
+            prose.severity = Some(ConfigDiagnosticSeverity::Warning);
+            config.dictionaries = vec![code, prose, Dictionary::new("names", "", "")];
+        }
+        let (spellchecker, requests) = mpsc::channel::<SpellRequest>();
+        *backend.spellchecker.write() = Some(spellchecker);
+        thread::spawn(move || {
+            while let Ok(SpellRequest::Check((word, _, send))) = requests.recv() {
+                let rejected_by = |languages: &[&str]| {
+                    languages.iter().map(|l| l.to_string()).collect::<Vec<_>>()
+                };
+                let result = match word.as_str() {
+                    "wrold" => (false, rejected_by(&["en-code", "en", "names"])),
+                    "colour" => (true, rejected_by(&["en-code"])),
+                    "kubectl" => (true, rejected_by(&["en"])),
+                    "jonh" => (true, rejected_by(&["names"])),
+                    _ => (true, vec![]),
+                };
+                let _ = send.send(result);
+            }
+        });
+
+        let diagnostics =
+            backend.spell_check_code(&Rope::from("wrold colour kubectl jonh"), "", None);
+
+        let severities = diagnostics
+            .iter()
+            .map(|d| (d.data.clone().unwrap(), d.severity.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            severities,
+            vec![
+                (Value::from("wrold"), DiagnosticSeverity::HINT),
+                (Value::from("colour"), DiagnosticSeverity::HINT),
+                (Value::from("kubectl"), DiagnosticSeverity::WARNING),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_keywords_of_the_document_language() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let code = Rope::from("impl Elsif for wrold");
+
+        let words = |language_id| {
+            backend
+                .misspelled_tokens(&code, language_id, None)
+                .into_iter()
+                .map(|t| t.lexeme)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words("rust"), vec!["Elsif", "wrold"]);
+        assert_eq!(words("ruby"), vec!["impl", "wrold"]);
+    }
+
+    #[test]
+    fn eager_suggestions_stop_when_the_budget_runs_out() {
+        let service = test_service();
+        let backend = service.inner();
+        {
+            let mut config = backend.config.write();
+            config.eager_suggestions = true;
+            config.suggestion_budget_ms = 50;
+        }
+        let (spellchecker, requests) = mpsc::channel::<SpellRequest>();
+        *backend.spellchecker.write() = Some(spellchecker);
+        thread::spawn(move || {
+            while let Ok(request) = requests.recv() {
+                match request {
+                    SpellRequest::Check((_, _, send)) => {
+                        let _ = send.send((false, vec![]));
+                    }
+                    SpellRequest::Suggest((_, send)) => {
+                        thread::sleep(Duration::from_millis(30));
+                        let _ = send.send(vec!["world".to_string()]);
+                    }
+                }
+            }
+        });
+
+        let diagnostics =
+            backend.spell_check_code(&Rope::from("wrold wrlod wordl wolrd wrodl"), "", None);
+
+        let eager = diagnostics
+            .iter()
+            .filter(|d| d.message.ends_with("did you mean \"world\"?"))
+            .count();
+        assert_eq!(diagnostics.len(), 5);
+        assert!((1..5).contains(&eager), "{eager} eager suggestions");
+    }
+
+    #[test]
+    fn labels_diagnostics_with_the_configured_source() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let code = Rope::from("wrold");
+
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(diagnostics[0].source.as_deref(), Some("rustproof"));
+
+        backend.config.write().diagnostic_source = "spelling (en)".to_string();
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(diagnostics[0].source.as_deref(), Some("spelling (en)"));
+    }
+
+    #[test]
+    fn counts_columns_in_the_negotiated_encoding() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["café"]);
+        let uri = open_document(backend, "café wrold");
+        *backend.position_encoding.write() = PositionEncoding::Utf8;
+
+        let code = backend.sources.get(&uri).unwrap().clone();
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(0, 6), Position::new(0, 11))
+        );
+
+        assert!(backend.replace_word_in_source(&uri, &diagnostics[0].range, "world"));
+        assert_eq!(backend.sources.get(&uri).unwrap().to_string(), "café world");
+    }
+
+    #[test]
+    fn checks_decomposed_words_in_their_composed_form() {
+        let service = test_service();
+        let backend = service.inner();
+        let checked = start_fake_checker(backend, &["café"]);
+
+        let code = Rope::from("cafe\u{301} wrold");
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(0, 6), Position::new(0, 11))
+        );
+        assert_eq!(*checked.lock(), vec!["café", "wrold"]);
+    }
+
+    #[test]
+    fn builds_the_variants_of_contractions_and_possessives() {
+        assert_eq!(contraction_variant("it's"), Some("it".to_string()));
+        assert_eq!(contraction_variant("Dont"), Some("Don't".to_string()));
+        assert_eq!(contraction_variant("CANT"), Some("CAN'T".to_string()));
+        assert_eq!(contraction_variant("can't"), None);
+        assert_eq!(contraction_variant("dogs"), None);
+    }
+
+    #[test]
+    fn checks_contractions_and_possessives_missing_from_the_dictionary() {
+        let service = test_service();
+        let backend = service.inner();
+        let checked = start_fake_checker(backend, &["can't", "don't", "it", "dogs", "toys"]);
+
+        let code = Rope::from("can't dont it's dogs' toys wont");
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].data, Some(Value::from("wont")));
+        assert_eq!(
+            *checked.lock(),
+            vec!["can't", "dont", "it's", "dogs", "toys", "wont", "don't", "it", "won't"]
+        );
+    }
+
+    #[test]
+    fn links_diagnostics_to_the_explanation_url() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let code = Rope::from("wrold");
+        assert_eq!(
+            backend.spell_check_code(&code, "", None)[0].code_description,
+            None
+        );
+
+        backend.config.write().explanation_url =
+            Some("https://example.com/spelling?lang=en".to_string());
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(
+            diagnostics[0]
+                .code_description
+                .as_ref()
+                .unwrap()
+                .href
+                .as_str(),
+            "https://example.com/spelling?lang=en&word=wrold"
+        );
+    }
+
+    #[test]
+    fn caps_the_diagnostics_of_a_document() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        backend.config.write().max_diagnostics_per_file = 2;
+
+        let code = Rope::from("helo wrold\nlnie");
+        let diagnostics = backend.spell_check_code(&code, "", None);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[2].message,
+            "Only the first 2 of 3 misspellings are reported"
+        );
+        assert_eq!(diagnostics[2].range.start, Position::new(1, 0));
+        assert_eq!(
+            diagnostics[2].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+    }
+
+    #[test]
+    fn skips_documents_larger_than_max_file_bytes() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let uri = open_document(backend, "helo wrold");
+        let source = backend.sources.get(&uri).unwrap().clone();
+        assert_eq!(backend.document_diagnostics(&uri, &source).len(), 2);
+
+        backend.config.write().max_file_bytes = 5;
+        assert!(backend.document_diagnostics(&uri, &source).is_empty());
+    }
+
+    #[test]
+    fn disable_directives_suppress_diagnostics() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+
+        let code = Rope::from("helo // rustproof-disable-line\nwrold");
+        let diagnostics = backend.spell_check_code(&code, "rust", None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 0));
+
+        let code = Rope::from("helo\n// rustproof-disable-file\nwrold");
+        assert!(backend.spell_check_code(&code, "rust", None).is_empty());
+    }
+
+    #[test]
+    fn checks_compounds_as_a_whole_first() {
+        let service = test_service();
+        let backend = service.inner();
+        // Stands in for a German dictionary compounding `Datei` and `System`
+        start_fake_checker(backend, &["dateisystem"]);
+        let code = Rope::from("DateiSystem DateiFehler");
+        let words = |backend: &Backend| {
+            backend
+                .misspelled_tokens(&code, "", None)
+                .into_iter()
+                .map(|t| t.lexeme)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words(backend), vec!["Datei", "System", "Datei", "Fehler"]);
+
+        backend.config.write().prefer_whole_word_check = true;
+        assert_eq!(words(backend), vec!["Datei", "Fehler"]);
+    }
+
+    #[test]
+    fn groups_diagnostics_by_word() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let uri = Url::parse("file:///project/document.txt").unwrap();
+
+        let diagnostics = backend.spell_check_code(&Rope::from("helo wrold helo\nhelo"), "", None);
+        let grouped = grouped_by_word(&uri, diagnostics);
+
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].message, "Unknown word \"helo\" (3 occurrences)");
+        assert_eq!(grouped[0].range.start, Position::new(0, 0));
+        let related = grouped[0].related_information.as_ref().unwrap();
+        assert_eq!(
+            related
+                .iter()
+                .map(|r| r.location.range.start)
+                .collect::<Vec<_>>(),
+            vec![Position::new(0, 11), Position::new(1, 0)]
+        );
+        assert_eq!(grouped[1].message, "Unknown word \"wrold\"");
+        assert_eq!(grouped[1].related_information, None);
+    }
+
+    #[test]
+    fn links_diagnostics_to_the_other_occurrences() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let uri = Url::parse("file:///project/document.txt").unwrap();
+
+        let diagnostics = backend.spell_check_code(&Rope::from("helo wrold helo\nhelo"), "", None);
+        let linked = with_related_occurrences(&uri, diagnostics);
+
+        assert_eq!(linked.len(), 4);
+        let related_starts = |d: &Diagnostic| {
+            d.related_information
+                .as_ref()
+                .map(|r| r.iter().map(|r| r.location.range.start).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            related_starts(&linked[0]),
+            Some(vec![Position::new(0, 11), Position::new(1, 0)])
+        );
+        assert_eq!(related_starts(&linked[1]), None);
+        assert_eq!(
+            related_starts(&linked[3]),
+            Some(vec![Position::new(0, 0), Position::new(0, 11)])
+        );
+    }
+
+    fn replace_with(uri: &Url, start: u32, end: u32, word: &str) -> ExecuteCommandParams {
+        command(
+            "replace.with.word",
+            vec![
+                Value::String(uri.to_string()),
+                serde_json::to_value(Range::new(Position::new(0, start), Position::new(0, end)))
+                    .unwrap(),
+                Value::String(word.to_string()),
+            ],
+        )
+    }
+
+    #[tokio::test]
+    async fn replacing_a_word_rechecks_the_edited_text() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &["hello", "world"]);
+        let uri = open_document(backend, "helo wrold");
+        backend.spell_check_uri(uri.clone()).await;
+
+        backend
+            .execute_command(replace_with(&uri, 0, 4, "hello"))
+            .await
+            .unwrap();
+        let diagnostics = backend.diagnostics.get(&uri).unwrap().clone();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(0, 6));
+
+        backend
+            .execute_command(replace_with(&uri, 6, 11, "wrld"))
+            .await
+            .unwrap();
+        let diagnostics = backend.diagnostics.get(&uri).unwrap().clone();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(0, 6), Position::new(0, 10))
+        );
+        assert_eq!(diagnostics[0].data, Some(Value::from("wrld")));
+    }
+
+    struct KnownWords(&'static [&'static str]);
+
+    impl SpellChecker for KnownWords {
+        fn check(&self, word: &str) -> bool {
+            self.0.contains(&word)
+        }
+
+        fn suggest(&self, _: &str) -> Vec<String> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn checker_threads_share_the_dictionaries_and_answer_every_request() {
+        let english: Box<dyn SpellChecker> = Box::new(KnownWords(&["hello"]));
+        let spellcheckers = Spellcheckers {
+            checkers: vec![("en".to_string(), Mutex::new(english))],
+            paths: vec![],
+            suggestion_engine: SuggestionEngine::Hunspell,
+            ranker: SuggestionRanker::new(config::SuggestionTiebreak::Frequency, ""),
+            symspell: OnceLock::new(),
+        };
+        let (requests, requests_rx) = mpsc::channel();
+        serve_spell_requests(4, requests_rx, Arc::new(spellcheckers));
+
+        let answers = (0..1000)
+            .map(|i| {
+                let word = if i % 2 == 0 { "hello" } else { "wrold" };
+                let (send, answer) = oneshot::channel();
+                let request = (word.to_string(), None, send);
+                requests.send(SpellRequest::Check(request)).unwrap();
+                (word, answer)
+            })
+            .collect::<Vec<_>>();
+
+        for (word, answer) in answers {
+            let (known, _) = answer.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(known, word == "hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn reloads_the_configuration_on_change() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let uri = open_document(backend, "wrold");
+        backend.spell_check_uri(uri.clone()).await;
+        let severity = |backend: &Backend| backend.diagnostics.get(&uri).unwrap()[0].severity;
+        assert_eq!(severity(backend), Some(DiagnosticSeverity::ERROR));
+
+        backend
+            .did_change_configuration(DidChangeConfigurationParams {
+                settings: serde_json::json!({
+                    "rustproof": { "diagnostic_severity": "hint" }
+                }),
+            })
+            .await;
+        assert_eq!(severity(backend), Some(DiagnosticSeverity::HINT));
+
+        // Invalid settings keep the current configuration
+        backend
+            .did_change_configuration(DidChangeConfigurationParams {
+                settings: serde_json::json!({ "diagnostic_severity": "loud" }),
+            })
+            .await;
+        assert_eq!(severity(backend), Some(DiagnosticSeverity::HINT));
+    }
+
+    #[tokio::test]
+    async fn reloads_the_workspace_dictionary_when_its_path_changes() {
+        let service = test_service();
+        let backend = service.inner();
+        let folder = std::env::temp_dir().join("rustproof-moved-workspace-dict");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("words.txt"), "kubectl\n").unwrap();
+        *backend.workspace_folders.write() = vec![folder.clone()];
+        start_fake_checker(backend, &[]);
+        let uri = Url::from_file_path(folder.join("notes.txt")).unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "".to_string(),
+                    1,
+                    "kubectl".to_string(),
+                ),
+            })
+            .await;
+        assert_eq!(backend.diagnostics.get(&uri).unwrap().len(), 1);
+
+        backend
+            .did_change_configuration(DidChangeConfigurationParams {
+                settings: serde_json::json!({
+                    "rustproof": { "workspace_dict_path": "words.txt" }
+                }),
+            })
+            .await;
+
+        assert!(backend.diagnostics.get(&uri).unwrap().is_empty());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[tokio::test]
+    async fn skips_tokens_matching_an_ignore_regex() {
+        let service = test_service();
+        let backend = service.inner();
+        backend
+            .load_config(InitializeParams {
+                initialization_options: Some(serde_json::json!({
+                    "ignore_regex": ["x[0-9a-f]{7}", "[A-Za-z0-9+/]{16,}={0,2}", "(unclosed"]
+                })),
+                ..Default::default()
+            })
+            .await;
+        start_fake_checker(backend, &[]);
+        let code = Rope::from("let x7f3abcde = \"aGVsbG8gd29ybGQgaGVsbG8=\"; // wrold");
+
+        let tokens = backend.misspelled_tokens(&code, "", None);
+
+        assert_eq!(backend.ignore_regexes.read().len(), 2);
+        let words = tokens.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>();
+        assert_eq!(words, vec!["wrold"]);
+    }
+
+    #[test]
+    fn anchored_ignore_regex_matches_whole_identifiers() {
+        let service = test_service();
+        let backend = service.inner();
+        *backend.ignore_regexes.write() = vec![Regex::new(r"^[A-Z]{2,}\d*$").unwrap()];
+        start_fake_checker(backend, &[]);
+        let code = Rope::from("HTTPS2 and TLSV, but Wrold2");
+
+        let tokens = backend.misspelled_tokens(&code, "", None);
+
+        let words = tokens.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>();
+        assert_eq!(words, vec!["Wrold"]);
+    }
+
+    #[test]
+    fn skips_overly_long_and_random_tokens() {
+        let service = test_service();
+        let backend = service.inner();
+        start_fake_checker(backend, &[]);
+        let code = Rope::from(
+            "Donaudampfschifffahrtsgesellschaft \"dGhpcyBpcyBhIHRlc3Q=\"\n\
+             Donaudampfschifffahrtsgesellschaftskapitaen",
+        );
+        let words = |backend: &Backend| {
+            backend
+                .misspelled_tokens(&code, "", None)
+                .into_iter()
+                .map(|t| t.lexeme)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            words(backend),
+            vec!["Donaudampfschifffahrtsgesellschaft", "Ghpcy", "Bpcy"]
+        );
+
+        backend.config.write().skip_random_strings = true;
+        assert_eq!(words(backend), vec!["Donaudampfschifffahrtsgesellschaft"]);
+
+        backend.config.write().max_word_length = usize::MAX;
+        assert_eq!(words(backend).len(), 2);
+    }
+
+    #[test]
+    fn comments_strings_scope_checks_heredocs_and_raw_strings() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().check_scope = CheckScope::CommentsStrings;
+        start_fake_checker(backend, &[]);
+        let code = Rope::from("let recieve = r#\"\nthe wrold\n\"#;");
+
+        let rust = backend.misspelled_tokens(&code, "rust", None);
+        assert_eq!(rust.len(), 1);
+        assert_eq!(rust[0].lexeme, "wrold");
+        assert_eq!((rust[0].start.line, rust[0].start.col), (1, 4));
+
+        // Unknown languages are checked entirely
+        let unknown = backend.misspelled_tokens(&code, "plaintext", None);
+        assert_eq!(unknown.len(), 2);
+    }
+
+    #[test]
+    fn comments_strings_scope_only_checks_prose_attribute_arguments() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().check_scope = CheckScope::CommentsStrings;
+        start_fake_checker(backend, &[]);
+        let code = Rope::from("#[doc = \"documentaton\"]\n#[serde(rename = \"fieldd\")]");
+
+        let tokens = backend.misspelled_tokens(&code, "rust", None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].lexeme, "documentaton");
+    }
+
+    #[test]
+    fn spell_check_times_out_on_a_slow_checker() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().operation_timeout_ms = 10;
+        let (spellchecker, requests) = mpsc::channel::<SpellRequest>();
+        *backend.spellchecker.write() = Some(spellchecker);
+        thread::spawn(move || {
+            while let Ok(SpellRequest::Check((_, _, send))) = requests.recv() {
+                thread::sleep(Duration::from_millis(500));
+                let _ = send.send((false, vec![]));
+            }
+        });
+
+        let started = std::time::Instant::now();
+        assert!(backend.spell_check("wrold", None));
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn check_words_share_one_timeout() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().operation_timeout_ms = 100;
+        let (spellchecker, requests) = mpsc::channel::<SpellRequest>();
+        *backend.spellchecker.write() = Some(spellchecker);
+        thread::spawn(move || {
+            while let Ok(SpellRequest::Check((_, _, send))) = requests.recv() {
+                thread::sleep(Duration::from_millis(60));
+                let _ = send.send((false, vec![]));
+            }
+        });
+
+        let words = ["helo", "wrold", "anotehr", "lnie", "agian", "tpyo"];
+        let started = std::time::Instant::now();
+        backend.check_words(words.into_iter(), None);
+        // Each word arrives within the timeout, all of them don't
+        assert!(started.elapsed() < Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn exports_and_imports_dictionary_files() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.config.write().dict_path = temp_dict_path("export-dict");
+        backend.insert_into_local_dict("wrold");
+        backend.insert_into_local_dict("kubernetes");
+        let exported = temp_dict_path("exported");
+
+        let result = backend
+            .execute_command(command(
+                "export.dict",
+                vec![Value::String(exported.to_string_lossy().to_string())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(result, Some(Value::from("kubernetes\nwrold\n")));
+        assert_eq!(
+            fs::read_to_string(&exported).unwrap(),
+            "kubernetes\nwrold\n"
+        );
+
+        backend.config.write().dict_path = temp_dict_path("import-dict");
+        backend.replace_local_dict(&["helo".to_string(), "wrold".to_string()]);
+        backend
+            .execute_command(command(
+                "import.dict",
+                vec![Value::String(exported.to_string_lossy().to_string())],
+            ))
+            .await
+            .unwrap();
+        fs::remove_file(&exported).unwrap();
+
+        assert_eq!(
+            backend.local_dict.words(),
+            vec!["helo", "kubernetes", "wrold"]
+        );
+        let dict_path = backend.config.read().dict_path.clone();
+        assert_eq!(
+            fs::read_to_string(dict_path).unwrap(),
+            "helo\nwrold\nkubernetes\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn dictionary_state_round_trips() {
+        let service = test_service();
+        let source = service.inner();
+        let folder = std::env::temp_dir().join("rustproof-state-folder");
+        let _ = fs::remove_dir_all(&folder);
+        *source.workspace_folders.write() = vec![folder.clone()];
+        source.config.write().dict_path = temp_dict_path("export-source");
+        source.insert_into_local_dict("kubernetes");
+        source.insert_into_local_dict("rustproof");
+        source.insert_into_folder_dict(&folder, "kubectl");
+        source.session_words.insert("wrold".to_string());
+
+        let state = source
+            .execute_command(command("dict.export", vec![]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            state,
+            serde_json::json!({
+                "words": ["kubernetes", "rustproof"],
+                "workspace_words": { folder.to_string_lossy(): ["kubectl"] },
+                "session_words": ["wrold"],
+            })
+        );
+        fs::remove_dir_all(&folder).unwrap();
+
+        let service = test_service();
+        let target = service.inner();
+        let target_path = temp_dict_path("export-target");
+        *target.workspace_folders.write() = vec![folder.clone()];
+        target.config.write().dict_path = target_path.clone();
+        target.insert_into_local_dict("stale");
+        target.insert_into_folder_dict(&folder, "helm");
+        target.session_words.insert("helo".to_string());
+
+        target
+            .execute_command(command(
+                "dict.import.state",
+                vec![state.clone(), Value::String("merge".to_string())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            target.local_dict.words(),
+            vec!["kubernetes", "rustproof", "stale"]
+        );
+        assert_eq!(
+            target.workspace_dicts.get(&folder).unwrap().words(),
+            vec!["helm", "kubectl"]
+        );
+        assert_eq!(target.session_words.words(), vec!["helo", "wrold"]);
+
+        target
+            .execute_command(command(
+                "dict.import.state",
+                vec![state, Value::String("replace".to_string())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(target.local_dict.words(), vec!["kubernetes", "rustproof"]);
+        assert_eq!(
+            fs::read_to_string(&target_path).unwrap(),
+            "kubernetes\nrustproof\n"
+        );
+        assert_eq!(
+            fs::read_to_string(folder.join(".rustproof/words.txt")).unwrap(),
+            "kubectl\n"
+        );
+        assert_eq!(target.session_words.words(), vec!["wrold"]);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn locally_known_words_skip_hunspell() {
+        let service = test_service();
+        let backend = service.inner();
+        backend.local_dict.insert("kubernetes".to_string());
+        let checked = start_fake_checker(backend, &[]);
+
+        let tokens = backend.misspelled_tokens(&Rope::from("kubernetes wrold"), "", None);
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(*checked.lock(), vec!["wrold"]);
+    }
+
+    #[test]
+    fn checks_and_suggests_nothing_before_the_checker_starts() {
+        let service = test_service();
+        let backend = service.inner();
+
+        assert!(backend
+            .misspelled_tokens(&Rope::from("helo wrold"), "", None)
+            .is_empty());
+        assert!(backend.suggest("wrold", None).is_empty());
+    }
+
+    #[test]
+    fn repeated_words_are_checked_once() {
+        let service = test_service();
+        let backend = service.inner();
+        let checked = start_fake_checker(backend, &["hello"]);
+        let code = Rope::from("hello wrold hello\nwrold");
+
+        assert_eq!(backend.misspelled_tokens(&code, "", None).len(), 2);
+        assert_eq!(backend.misspelled_tokens(&code, "", None).len(), 2);
+
+        assert_eq!(*checked.lock(), vec!["hello", "wrold"]);
+    }
+
+    /// Needs the default dictionaries, they are downloaded on the first run.
+    /// Run with `cargo test --release bench_spellchecker -- --ignored --nocapture`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_spellchecker() {
+        let line = "The quick brown fox jumps over the lazy dog, recieve the wrold.\n";
+        let code = Rope::from(line.repeat(5000).as_str());
+        let service = test_service();
+        let backend = service.inner();
+        backend.start_spellchecker().await;
+
+        let started = Instant::now();
+        let misspelled = backend.misspelled_tokens(&code, "", None).len();
+        println!("{:?} for {misspelled} misspellings", started.elapsed());
+    }
 }
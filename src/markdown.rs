@@ -0,0 +1,74 @@
+use crate::lexer::{Location, Pos};
+use crate::skipped_spans::{Run, SkippedKind};
+
+/// Markdown link and image targets, i.e. the parenthesized part of
+/// `[text](target)` and `![alt](target)`. The link text and alt text are
+/// left alone so they still get spell-checked.
+pub const LINK_TARGETS: SkippedKind = SkippedKind {
+    is_part: |char| char != '\n',
+    spans_in: link_targets_in,
+};
+
+/// Targets within a line, they don't span lines
+fn link_targets_in(run: &Run) -> Vec<Location> {
+    let mut targets = vec![];
+    let mut prev: Option<char> = None;
+    let mut target_start: Option<Pos> = None;
+
+    for &(char, pos) in run.chars {
+        match (char, target_start) {
+            (')', Some(start)) => {
+                targets.push(Location::new(start, pos));
+                target_start = None;
+            }
+            ('(', None) if prev == Some(']') => {
+                target_start = Some(pos.set_col(pos.col + 1));
+            }
+            _ => {}
+        }
+        prev = Some(char);
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::position::PositionEncoding;
+    use crate::skipped_spans::SkippedSpans;
+
+    fn checked_words(str: &str) -> String {
+        let targets = SkippedSpans::new(str.chars(), PositionEncoding::Utf16, &[&LINK_TARGETS]);
+        Lexer::new(str.chars())
+            .filter(|t| !targets.contains(t))
+            .map(|t| t.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn it_skips_link_urls_but_checks_link_text() {
+        let str = "See [the documentaton](https://exampel.com/some-guide) for more";
+        assert_eq!(checked_words(str), "See the documentaton for more");
+    }
+
+    #[test]
+    fn it_skips_image_paths_but_checks_alt_text() {
+        let str = "![archtecture diagram](images/diagrm.png)";
+        assert_eq!(checked_words(str), "archtecture diagram");
+    }
+
+    #[test]
+    fn it_keeps_parentheses_that_are_not_links() {
+        let str = "call(something) [brackets] (parens)";
+        assert_eq!(checked_words(str), "call something brackets parens");
+    }
+
+    #[test]
+    fn it_does_not_span_targets_across_lines() {
+        let str = "[text](unclosed\nnext line)";
+        assert_eq!(checked_words(str), "text unclosed next line");
+    }
+}
@@ -0,0 +1,65 @@
+use crate::lexer::Location;
+use crate::skipped_spans::{Run, SkippedKind};
+
+/// Numeric literals such as `0xDEADBEEF`, `42px`, `255u8` or `v2`.
+/// The lexer only keeps letters so these would otherwise turn into tokens
+/// like `xDEADBEEF` or `px`.
+pub const NUMERIC_LITERALS: SkippedKind = SkippedKind {
+    is_part: |char| char.is_alphanumeric() || char == '_',
+    spans_in: numeric_literal_in,
+};
+
+/// A run of alphanumeric characters and underscores is a number if it
+/// starts with a digit or has at least as many digits as letters
+fn numeric_literal_in(run: &Run) -> Vec<Location> {
+    let starts_with_digit = run.chars[0].0.is_ascii_digit();
+    let digits = run.chars.iter().filter(|(c, _)| c.is_ascii_digit()).count();
+    let letters = run.chars.iter().filter(|(c, _)| c.is_alphabetic()).count();
+    if starts_with_digit || (digits > 0 && digits >= letters) {
+        vec![run.location(0, run.chars.len())]
+    } else {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::position::PositionEncoding;
+    use crate::skipped_spans::SkippedSpans;
+
+    fn checked_words(str: &str) -> String {
+        let literals =
+            SkippedSpans::new(str.chars(), PositionEncoding::Utf16, &[&NUMERIC_LITERALS]);
+        Lexer::new(str.chars())
+            .filter(|t| !literals.contains(t))
+            .map(|t| t.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn it_skips_hex_literals() {
+        assert_eq!(checked_words("let mask = 0xDEADBEEF;"), "let mask");
+        assert_eq!(checked_words("0b1010 and 255u8"), "and");
+    }
+
+    #[test]
+    fn it_skips_numbers_with_units() {
+        assert_eq!(checked_words("width: 42px;"), "width");
+    }
+
+    #[test]
+    fn it_skips_words_that_are_mostly_digits() {
+        assert_eq!(checked_words("api v2 x86"), "api");
+    }
+
+    #[test]
+    fn it_keeps_identifiers_containing_digits() {
+        assert_eq!(
+            checked_words("utf8Decoder base64_encode"),
+            "utf Decoder base encode"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::lexer::Location;
+use crate::skipped_spans::{is_not_whitespace, Run, SkippedKind};
+
+const PATH_SEPARATORS: [char; 2] = ['/', '\\'];
+const ROOTS: [&str; 6] = ["./", "../", "~/", ".\\", "..\\", "\\\\"];
+
+/// File paths such as `src/main.rs` or `../utils/helper.js`.
+/// The lexer drops the slashes and dots joining their parts so these would
+/// otherwise turn into tokens like `src`, `main` and `rs`.
+pub const FILE_PATHS: SkippedKind = SkippedKind {
+    is_part: is_not_whitespace,
+    spans_in: path_in,
+};
+
+/// The location of a run of non-whitespace characters if it is a path
+fn path_in(run: &Run) -> Vec<Location> {
+    let chars = run.chars.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+    path_bounds(&chars)
+        .map(|(start, stop)| run.location(start, stop))
+        .into_iter()
+        .collect()
+}
+
+/// The path without the quotes, brackets and punctuation around it
+fn path_bounds(chars: &[char]) -> Option<(usize, usize)> {
+    let start = chars
+        .iter()
+        .position(|c| !matches!(c, '"' | '\'' | '`' | '(' | '[' | '{' | '<'))?;
+    let stop = chars.iter().rposition(|c| {
+        !matches!(
+            c,
+            '"' | '\'' | '`' | ')' | ']' | '}' | '>' | ',' | ';' | ':' | '!' | '?' | '.'
+        )
+    })? + 1;
+    if start >= stop {
+        return None;
+    }
+    is_path(&chars[start..stop].iter().collect::<String>()).then_some((start, stop))
+}
+
+/// Slashes alone also join words in prose like `and/or`, a path has to
+/// start at a root, end with a separator or name a file with an extension
+fn is_path(text: &str) -> bool {
+    if !text.contains(PATH_SEPARATORS) {
+        return false;
+    }
+    let rooted = ROOTS.iter().any(|root| text.starts_with(root))
+        // Not a `//` comment marker touching the comment
+        || (text.starts_with('/') && !text.starts_with("//"))
+        || is_drive(text);
+    let is_dir = text.ends_with(PATH_SEPARATORS);
+    let file_name = text.rsplit(PATH_SEPARATORS).next().unwrap_or_default();
+    let has_extension = file_name.rsplit_once('.').is_some_and(|(name, extension)| {
+        !name.is_empty()
+            && (1..=5).contains(&extension.len())
+            && extension.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    rooted || is_dir || has_extension
+}
+
+/// Windows paths like `C:\Users`
+fn is_drive(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.next() == Some(':')
+        && chars.next().is_some_and(|c| PATH_SEPARATORS.contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::position::PositionEncoding;
+    use crate::skipped_spans::SkippedSpans;
+
+    fn checked_words(str: &str) -> String {
+        let paths = SkippedSpans::new(str.chars(), PositionEncoding::Utf16, &[&FILE_PATHS]);
+        Lexer::new(str.chars())
+            .filter(|t| !paths.contains(t))
+            .map(|t| t.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn it_skips_relative_paths() {
+        assert_eq!(
+            checked_words("see src/main.rs for details"),
+            "see for details"
+        );
+        assert_eq!(
+            checked_words("import from ../utils/helper.js"),
+            "import from"
+        );
+        assert_eq!(checked_words("run ./scripts/setup"), "run");
+    }
+
+    #[test]
+    fn it_skips_absolute_paths() {
+        assert_eq!(checked_words("edit /etc/hosts now"), "edit now");
+        assert_eq!(checked_words("open ~/.config/rustproof"), "open");
+        assert_eq!(checked_words(r"copy C:\Users\helo\file"), "copy");
+    }
+
+    #[test]
+    fn it_skips_paths_in_quotes_and_brackets() {
+        assert_eq!(
+            checked_words("Reads \"config/settings.toml\" (see docs/guide.md)."),
+            "Reads see"
+        );
+    }
+
+    #[test]
+    fn it_keeps_words_joined_by_slashes_in_prose() {
+        assert_eq!(checked_words("read and/or write"), "read and or write");
+        assert_eq!(checked_words("//helo wrold"), "helo wrold");
+        assert_eq!(checked_words("e.g. this"), "e g this");
+    }
+}
@@ -0,0 +1,150 @@
+use crate::lexer::Pos;
+use crop::Rope;
+use tower_lsp::lsp_types::{InitializeParams, Position, PositionEncodingKind};
+
+/// The unit the columns exchanged with the client count in
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PositionEncoding {
+    Utf8,
+    /// Supported by every client, see the LSP spec
+    #[default]
+    Utf16,
+}
+
+impl PositionEncoding {
+    /// UTF-8 if the client offers it since it matches the byte offsets of
+    /// the rope, UTF-16 otherwise
+    pub fn negotiate(init: &InitializeParams) -> Self {
+        let offered = init
+            .capabilities
+            .general
+            .as_ref()
+            .and_then(|g| g.position_encodings.as_ref());
+        match offered {
+            Some(encodings) if encodings.contains(&PositionEncodingKind::UTF8) => Self::Utf8,
+            _ => Self::Utf16,
+        }
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+        }
+    }
+
+    /// Columns taken up by `char`
+    pub fn char_len(self, char: char) -> u32 {
+        match self {
+            Self::Utf8 => char.len_utf8() as u32,
+            Self::Utf16 => char.len_utf16() as u32,
+        }
+    }
+
+    /// Columns taken up by `text`
+    pub fn str_len(self, text: &str) -> u32 {
+        text.chars().map(|c| self.char_len(c)).sum()
+    }
+}
+
+impl From<Position> for Pos {
+    fn from(position: Position) -> Self {
+        Pos {
+            line: position.line,
+            col: position.character,
+        }
+    }
+}
+
+/// Converts an LSP position, whose column counts `encoding` units, to a byte
+/// offset in `rope`. Columns past the end of a line are clamped to the end of
+/// the line, lines past the end of the rope to the end of the rope.
+pub fn byte_offset_for_position(rope: &Rope, pos: &Pos, encoding: PositionEncoding) -> usize {
+    let line = pos.line as usize;
+    if line >= rope.line_len() {
+        return rope.byte_len();
+    }
+    let mut offset = rope.byte_of_line(line);
+    let mut col = 0;
+    for char in rope.line(line).chars() {
+        if col >= pos.col {
+            break;
+        }
+        col += encoding.char_len(char);
+        offset += char.len_utf8();
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::{ClientCapabilities, GeneralClientCapabilities};
+
+    const UTF16: PositionEncoding = PositionEncoding::Utf16;
+
+    fn pos(line: u32, col: u32) -> Pos {
+        Pos { line, col }
+    }
+
+    #[test]
+    fn it_converts_ascii_positions() {
+        let rope = Rope::from("hello\nworld");
+        assert_eq!(byte_offset_for_position(&rope, &pos(0, 0), UTF16), 0);
+        assert_eq!(byte_offset_for_position(&rope, &pos(1, 2), UTF16), 8);
+    }
+
+    #[test]
+    fn it_converts_multibyte_positions() {
+        // é is 2 bytes but a single UTF-16 code unit
+        let rope = Rope::from("café wrold");
+        assert_eq!(byte_offset_for_position(&rope, &pos(0, 5), UTF16), 6);
+    }
+
+    #[test]
+    fn it_converts_surrogate_pair_positions() {
+        // 🤖 is 4 bytes and two UTF-16 code units
+        let rope = Rope::from("a\n🤖 helo");
+        assert_eq!(byte_offset_for_position(&rope, &pos(1, 3), UTF16), 7);
+    }
+
+    #[test]
+    fn it_clamps_positions_past_the_end() {
+        let rope = Rope::from("ab\ncd");
+        assert_eq!(byte_offset_for_position(&rope, &pos(0, 10), UTF16), 2);
+        assert_eq!(byte_offset_for_position(&rope, &pos(5, 0), UTF16), 5);
+    }
+
+    #[test]
+    fn it_converts_utf8_positions() {
+        let rope = Rope::from("a\n🤖 café");
+        let utf8 = PositionEncoding::Utf8;
+        assert_eq!(byte_offset_for_position(&rope, &pos(1, 8), utf8), 10);
+    }
+
+    #[test]
+    fn it_negotiates_utf8_when_offered() {
+        let init = |encodings: Option<Vec<PositionEncodingKind>>| InitializeParams {
+            capabilities: ClientCapabilities {
+                general: Some(GeneralClientCapabilities {
+                    position_encodings: encodings,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            PositionEncoding::negotiate(&init(Some(vec![
+                PositionEncodingKind::UTF16,
+                PositionEncodingKind::UTF8
+            ]))),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(&init(Some(vec![PositionEncodingKind::UTF32]))),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(PositionEncoding::negotiate(&init(None)), UTF16);
+    }
+}
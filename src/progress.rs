@@ -0,0 +1,60 @@
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressCreateParams, WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+use tower_lsp::Client;
+
+/// Work done progress shown by the editor, e.g. in its status line,
+/// until it is ended
+pub struct WorkDone {
+    client: Client,
+    token: NumberOrString,
+}
+
+impl WorkDone {
+    /// `None` if the client refused to create the progress `token`
+    pub async fn begin(client: &Client, token: &str, title: &str) -> Option<Self> {
+        let token = NumberOrString::String(token.to_string());
+        client
+            .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .ok()?;
+        let work_done = Self {
+            client: client.clone(),
+            token,
+        };
+        work_done
+            .send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.to_string(),
+                ..Default::default()
+            }))
+            .await;
+        Some(work_done)
+    }
+
+    pub async fn report(&self, message: String) {
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            message: Some(message),
+            ..Default::default()
+        }))
+        .await
+    }
+
+    pub async fn end(self) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd::default()))
+            .await
+    }
+
+    async fn send(&self, progress: WorkDoneProgress) {
+        self.client
+            .send_notification::<Progress>(ProgressParams {
+                token: self.token.clone(),
+                value: ProgressParamsValue::WorkDone(progress),
+            })
+            .await
+    }
+}
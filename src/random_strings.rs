@@ -0,0 +1,95 @@
+use crate::expander::Expander;
+use crate::lexer::Location;
+use crate::skipped_spans::{Run, SkippedKind};
+use std::collections::HashSet;
+
+/// Shorter runs are judged as words, hashes and keys are usually longer
+const MIN_RANDOM_LENGTH: usize = 12;
+
+/// Generated strings such as base64 blobs, hashes or API keys.
+/// The lexer splits them at their digits, leaving tokens like `aGVsbG` or
+/// `ybGQ` that would all be reported.
+pub const RANDOM_STRINGS: SkippedKind = SkippedKind {
+    is_part: char::is_alphanumeric,
+    spans_in: random_string_in,
+};
+
+fn random_string_in(run: &Run) -> Vec<Location> {
+    if looks_random(&run.text()) {
+        vec![run.location(0, run.chars.len())]
+    } else {
+        vec![]
+    }
+}
+
+/// A run of letters and digits looks random when splitting it like an
+/// identifier leaves tiny parts, e.g. `aGVsbG8gd29y` becomes `a G Vsb G 8 gd
+/// 29 y`, or when it has hardly any vowels but many distinct letters
+fn looks_random(run: &str) -> bool {
+    let len = run.chars().count();
+    if len < MIN_RANDOM_LENGTH {
+        return false;
+    }
+    let parts = Expander::new(run.chars()).count();
+    // Identifiers like `toUpperCaseIfNeeded` average more than 3 characters a part
+    if len * 2 < parts * 5 {
+        return true;
+    }
+    // Other scripts don't have the same vowels
+    if !run.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return false;
+    }
+    let letters = run.chars().filter(|c| c.is_ascii_alphabetic()).count();
+    let vowels = run
+        .chars()
+        .filter(|c| "aeiouy".contains(c.to_ascii_lowercase()))
+        .count();
+    let unique = run.chars().collect::<HashSet<_>>().len();
+    vowels * 100 < letters * 15 && unique * 2 >= len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::position::PositionEncoding;
+    use crate::skipped_spans::SkippedSpans;
+
+    fn checked_words(str: &str) -> String {
+        let strings = SkippedSpans::new(str.chars(), PositionEncoding::Utf16, &[&RANDOM_STRINGS]);
+        Lexer::new(str.chars())
+            .filter(|t| !strings.contains(t))
+            .map(|t| t.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn it_skips_base64_strings() {
+        assert_eq!(
+            checked_words("let token = \"dGhpcyBpcyBhIHRlc3Q=\";"),
+            "let token"
+        );
+    }
+
+    #[test]
+    fn it_skips_hashes_and_keys() {
+        assert_eq!(
+            checked_words("commit d41d8cd98f00b204e9800998ecf8427e"),
+            "commit"
+        );
+        assert_eq!(checked_words("key = xkcdqzvwpmtrblfg"), "key");
+    }
+
+    #[test]
+    fn it_keeps_long_words_and_identifiers() {
+        assert_eq!(
+            checked_words("Donaudampfschifffahrtsgesellschaft toUpperCaseIfNeeded"),
+            "Donaudampfschifffahrtsgesellschaft toUpperCaseIfNeeded"
+        );
+        assert_eq!(
+            checked_words("base64EncodedString XMLHttpRequest"),
+            "base EncodedString XMLHttpRequest"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::lexer::{Pos, Token};
+use serde::Serialize;
+
+/// The column a human sees in an editor that expands tabs to `tab_width`
+/// columns, for a column given in UTF-16 code units as used by LSP.
+/// Only meant for human-readable output, LSP positions stay in code units.
+pub fn display_column(line: &str, col: u32, tab_width: u32) -> u32 {
+    let tab_width = tab_width.max(1);
+    let mut units = 0;
+    let mut display = 0;
+    for char in line.chars() {
+        if units >= col {
+            break;
+        }
+        units += char.len_utf16() as u32;
+        display = match char {
+            '\t' => (display / tab_width + 1) * tab_width,
+            _ => display + 1,
+        };
+    }
+    display
+}
+
+/// Formats a misspelling as `path:line:column: message` with 1-based
+/// line and column, `line` is the text of the line the token is on.
+pub fn format_misspelling(path: &str, line: &str, token: &Token, tab_width: u32) -> String {
+    format!(
+        "{}:{}:{}: Unknown word \"{}\"",
+        path,
+        token.start.line + 1,
+        display_column(line, token.start.col, tab_width) + 1,
+        token.lexeme
+    )
+}
+
+/// A misspelling in machine-readable output, the position is 1-based
+/// like in `format_misspelling`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Finding {
+    pub file: String,
+    #[serde(flatten)]
+    pub pos: Pos,
+    pub word: String,
+    pub suggestions: Vec<String>,
+}
+
+impl Finding {
+    pub fn new(
+        path: &str,
+        line: &str,
+        token: &Token,
+        tab_width: u32,
+        suggestions: Vec<String>,
+    ) -> Finding {
+        Finding {
+            file: path.to_string(),
+            pos: Pos {
+                line: token.start.line + 1,
+                col: display_column(line, token.start.col, tab_width) + 1,
+            },
+            word: token.lexeme.clone(),
+            suggestions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn report(text: &str, tab_width: u32) -> Vec<String> {
+        Lexer::new(text.chars())
+            .map(|t| {
+                let line = text.lines().nth(t.start.line as usize).unwrap();
+                format_misspelling("notes.txt", line, &t, tab_width)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_expands_tabs_to_tab_stops() {
+        assert_eq!(display_column("\t\twrold", 2, 4), 8);
+        assert_eq!(display_column("ab\twrold", 3, 4), 4);
+        assert_eq!(display_column("\twrold", 1, 1), 1);
+    }
+
+    #[test]
+    fn it_counts_characters_instead_of_code_units() {
+        // 🤖 is two UTF-16 code units but a single column
+        assert_eq!(display_column("🤖 wrold", 3, 4), 2);
+    }
+
+    #[test]
+    fn it_aligns_report_columns_with_the_tab_width() {
+        let text = "fn main() {\n\tlet wrold = 1;\n}";
+        assert_eq!(report(text, 4)[2], "notes.txt:2:5: Unknown word \"let\"");
+        assert_eq!(report(text, 4)[3], "notes.txt:2:9: Unknown word \"wrold\"");
+        assert_eq!(report(text, 1)[3], "notes.txt:2:6: Unknown word \"wrold\"");
+    }
+
+    #[test]
+    fn it_serializes_findings_with_flat_positions() {
+        let text = "fn main() {\n\tlet wrold = 1;\n}";
+        let token = Lexer::new(text.chars()).nth(3).unwrap();
+        let finding = Finding::new(
+            "notes.txt",
+            "\tlet wrold = 1;",
+            &token,
+            4,
+            vec!["world".into()],
+        );
+        assert_eq!(
+            serde_json::to_value(finding).unwrap(),
+            serde_json::json!({
+                "file": "notes.txt",
+                "line": 2,
+                "col": 9,
+                "word": "wrold",
+                "suggestions": ["world"],
+            })
+        );
+    }
+}
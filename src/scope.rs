@@ -0,0 +1,706 @@
+use crate::lexer::{Location, Pos};
+use crate::position::PositionEncoding;
+use std::ops::Range;
+
+/// The kind of source code a piece of text belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Code,
+    Comment,
+    String,
+}
+
+/// The bits of a language's grammar needed to find comments and strings
+pub struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    quotes: &'static [char],
+    /// Python style `"""` strings
+    triple_quotes: bool,
+    /// Rust style `r#"..."#` strings
+    raw_strings: bool,
+    /// Rust style `'a'` char literals, a lone `'` is a lifetime
+    char_literals: bool,
+    /// Shell and ruby style `<<EOF` heredocs
+    heredocs: bool,
+    /// Rust style `#[attribute]`
+    attributes: bool,
+    /// `@decorator(...)` as in typescript, java and python
+    decorators: bool,
+    /// printf style `%s` and `%(name)d` placeholders
+    printf_placeholders: bool,
+}
+
+const RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    quotes: &['"'],
+    triple_quotes: false,
+    raw_strings: true,
+    char_literals: true,
+    heredocs: false,
+    attributes: true,
+    decorators: false,
+    printf_placeholders: false,
+};
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    quotes: &['"', '\'', '`'],
+    triple_quotes: false,
+    raw_strings: false,
+    char_literals: false,
+    heredocs: false,
+    attributes: false,
+    decorators: true,
+    printf_placeholders: true,
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    quotes: &['"', '\''],
+    triple_quotes: true,
+    raw_strings: false,
+    char_literals: false,
+    heredocs: false,
+    attributes: false,
+    decorators: true,
+    printf_placeholders: true,
+};
+
+const RUBY: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[("=begin", "=end")],
+    quotes: &['"', '\'', '`'],
+    triple_quotes: false,
+    raw_strings: false,
+    char_literals: false,
+    heredocs: true,
+    attributes: false,
+    decorators: false,
+    printf_placeholders: true,
+};
+
+const SHELL: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    quotes: &['"', '\''],
+    triple_quotes: false,
+    raw_strings: false,
+    char_literals: false,
+    heredocs: true,
+    attributes: false,
+    decorators: false,
+    printf_placeholders: true,
+};
+
+const LUA: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("--[[", "]]")],
+    quotes: &['"', '\''],
+    triple_quotes: false,
+    raw_strings: false,
+    char_literals: false,
+    heredocs: false,
+    attributes: false,
+    decorators: false,
+    printf_placeholders: true,
+};
+
+const TOML: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    quotes: &['"', '\''],
+    triple_quotes: true,
+    raw_strings: false,
+    char_literals: false,
+    heredocs: false,
+    attributes: false,
+    decorators: false,
+    printf_placeholders: false,
+};
+
+impl Syntax {
+    /// Looks up the syntax for an LSP `languageId`,
+    /// `None` for prose and unsupported languages
+    pub fn for_language(language_id: &str) -> Option<&'static Syntax> {
+        match language_id {
+            "rust" => Some(&RUST),
+            "javascript" | "javascriptreact" | "typescript" | "typescriptreact" | "java" | "c"
+            | "cpp" | "csharp" | "go" | "swift" | "kotlin" | "scala" | "dart" => Some(&C_LIKE),
+            "python" => Some(&PYTHON),
+            "ruby" => Some(&RUBY),
+            "shellscript" | "sh" | "bash" | "zsh" => Some(&SHELL),
+            "lua" => Some(&LUA),
+            "toml" => Some(&TOML),
+            _ => None,
+        }
+    }
+}
+
+/// The comment and string spans of a document, everything else is code
+pub struct Scopes(Vec<(Location, Scope)>);
+
+impl Scopes {
+    /// Strings passed to attributes and decorators are code unless the
+    /// annotation is one of `prose_annotations`, e.g. `#[doc = "..."]`.
+    /// With `skip_placeholders` format placeholders such as `{name}` or
+    /// `%s` inside strings are code as well.
+    pub fn new<I: Iterator<Item = char>>(
+        syntax: &Syntax,
+        text: I,
+        prose_annotations: &[String],
+        skip_placeholders: bool,
+        encoding: PositionEncoding,
+    ) -> Self {
+        let chars: Vec<char> = text.collect();
+        // positions[i] is the position of chars[i], the last entry is the end of the text
+        let mut positions = Vec::with_capacity(chars.len() + 1);
+        let mut pos = Pos::start();
+        for char in &chars {
+            positions.push(pos);
+            pos = if *char == '\n' {
+                Pos {
+                    line: pos.line + 1,
+                    col: 0,
+                }
+            } else {
+                pos.set_col(pos.col + encoding.char_len(*char))
+            };
+        }
+        positions.push(pos);
+
+        let spans = Scanner {
+            syntax,
+            chars: &chars,
+            prose_annotations,
+            skip_placeholders,
+        }
+        .scan()
+        .into_iter()
+        .map(|(range, scope)| {
+            let location = Location::new(positions[range.start], positions[range.end]);
+            (location, scope)
+        })
+        .collect();
+        Self(spans)
+    }
+
+    pub fn scope_of(&self, pos: &Pos) -> Scope {
+        // Spans are sorted and never overlap
+        let idx = self
+            .0
+            .partition_point(|(l, _)| (l.start.line, l.start.col) <= (pos.line, pos.col));
+        match idx.checked_sub(1).map(|i| &self.0[i]) {
+            Some((location, scope)) if location.contains(pos) => *scope,
+            _ => Scope::Code,
+        }
+    }
+}
+
+struct Scanner<'a> {
+    syntax: &'a Syntax,
+    chars: &'a [char],
+    prose_annotations: &'a [String],
+    skip_placeholders: bool,
+}
+
+/// An attribute or decorator whose arguments are being scanned
+struct Annotation {
+    /// Whether its string arguments are checked
+    prose: bool,
+    open: char,
+    close: char,
+    depth: usize,
+}
+
+impl Scanner<'_> {
+    /// Comment and string spans as char index ranges
+    fn scan(&self) -> Vec<(Range<usize>, Scope)> {
+        let len = self.chars.len();
+        let mut spans = vec![];
+        let mut heredocs: Vec<String> = vec![];
+        let mut annotation: Option<Annotation> = None;
+        let mut i = 0;
+
+        while i < len {
+            if self.chars[i] == '\n' && !heredocs.is_empty() {
+                // Heredoc bodies start on the line after their marker
+                i += 1;
+                for terminator in heredocs.drain(..) {
+                    let body_start = i;
+                    while i < len && !self.is_heredoc_terminator(i, &terminator) {
+                        i = self.line_end(i) + 1;
+                    }
+                    let body_end = i.min(len);
+                    self.push_string(&mut spans, body_start..body_end);
+                    i = (self.line_end(body_end) + 1).min(len);
+                }
+                continue;
+            }
+            if let Some(end) = self.comment_end(i) {
+                spans.push((i..end, Scope::Comment));
+                i = end;
+                continue;
+            }
+            if let Some(end) = self.char_literal_end(i) {
+                i = end;
+                continue;
+            }
+            if let Some(end) = self.string_end(i) {
+                if annotation.as_ref().is_none_or(|a| a.prose) {
+                    self.push_string(&mut spans, i..end);
+                }
+                i = end;
+                continue;
+            }
+            if let Some((terminator, end)) = self.heredoc_marker(i) {
+                heredocs.push(terminator);
+                i = end;
+                continue;
+            }
+            if let Some(a) = annotation.as_mut() {
+                if self.chars[i] == a.open {
+                    a.depth += 1;
+                } else if self.chars[i] == a.close {
+                    a.depth -= 1;
+                    if a.depth == 0 {
+                        annotation = None;
+                    }
+                }
+            } else if let Some((found, end)) = self.annotation_start(i) {
+                annotation = Some(found);
+                i = end;
+                continue;
+            }
+            i += 1;
+        }
+
+        spans
+    }
+
+    /// Pushes the string at `range` as string spans around its placeholders
+    fn push_string(&self, spans: &mut Vec<(Range<usize>, Scope)>, range: Range<usize>) {
+        let mut start = range.start;
+        let mut k = range.start;
+        while k < range.end {
+            // Escaped braces and percent signs are literal text
+            if self.starts_with(k, "{{") || self.starts_with(k, "%%") {
+                k += 2;
+                continue;
+            }
+            match self.placeholder_end(k, range.end) {
+                Some(end) => {
+                    if start < k {
+                        spans.push((start..k, Scope::String));
+                    }
+                    start = end;
+                    k = end;
+                }
+                None => k += 1,
+            }
+        }
+        if start < range.end {
+            spans.push((start..range.end, Scope::String));
+        }
+    }
+
+    /// Parses format placeholders such as `{}`, `{0}`, `{name:>8}`, `%s`
+    /// and `%(name)d`, returns the index after the placeholder
+    fn placeholder_end(&self, at: usize, end: usize) -> Option<usize> {
+        if !self.skip_placeholders {
+            return None;
+        }
+        let mut k = at + 1;
+        match self.chars[at] {
+            '{' => {
+                while k < end && (self.is_ident_char(k) || self.chars[k] == '.') {
+                    k += 1;
+                }
+                // Format specs and conversions such as `{value:?}` or `{name!r}`
+                if k < end && matches!(self.chars[k], ':' | '!') {
+                    while k < end && !matches!(self.chars[k], '}' | '{' | '\n') {
+                        k += 1;
+                    }
+                }
+                (k < end && self.chars[k] == '}').then_some(k + 1)
+            }
+            '%' if self.syntax.printf_placeholders => {
+                if self.chars.get(k) == Some(&'(') {
+                    k += 1;
+                    while k < end && self.is_ident_char(k) {
+                        k += 1;
+                    }
+                    if self.chars.get(k) != Some(&')') {
+                        return None;
+                    }
+                    k += 1;
+                }
+                let skip = |k: &mut usize, chars: &str| {
+                    while *k < end && chars.contains(self.chars[*k]) {
+                        *k += 1;
+                    }
+                };
+                // Flags, width, precision and length modifiers
+                skip(&mut k, "-+#0");
+                skip(&mut k, "0123456789*");
+                if self.chars.get(k) == Some(&'.') {
+                    k += 1;
+                    skip(&mut k, "0123456789*");
+                }
+                skip(&mut k, "hlLqjzt");
+                let conversion = self.chars.get(k).filter(|_| k < end)?;
+                "diouxXeEfFgGaAcspn".contains(*conversion).then_some(k + 1)
+            }
+            _ => None,
+        }
+    }
+
+    fn starts_with(&self, at: usize, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(k, c)| self.chars.get(at + k) == Some(&c))
+    }
+
+    /// Index right after the first occurrence of `s` at or after `at`
+    fn find_end(&self, at: usize, s: &str) -> usize {
+        (at..self.chars.len())
+            .find(|k| self.starts_with(*k, s))
+            .map(|k| k + s.chars().count())
+            .unwrap_or(self.chars.len())
+    }
+
+    /// Index of the newline ending the line `at` is on
+    fn line_end(&self, at: usize) -> usize {
+        (at..self.chars.len())
+            .find(|k| self.chars[*k] == '\n')
+            .unwrap_or(self.chars.len())
+    }
+
+    fn is_ident_char(&self, at: usize) -> bool {
+        self.chars
+            .get(at)
+            .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+    }
+
+    fn comment_end(&self, at: usize) -> Option<usize> {
+        for (open, close) in self.syntax.block_comments {
+            if self.starts_with(at, open) {
+                return Some(self.find_end(at + open.chars().count(), close));
+            }
+        }
+        for prefix in self.syntax.line_comments {
+            // `#` is only a comment at the start of a word, think `$#` or `${#list}`
+            let at_word_start = at == 0 || self.chars[at - 1].is_whitespace();
+            if self.starts_with(at, prefix) && (*prefix != "#" || at_word_start) {
+                return Some(self.line_end(at));
+            }
+        }
+        None
+    }
+
+    fn char_literal_end(&self, at: usize) -> Option<usize> {
+        if !self.syntax.char_literals || self.chars[at] != '\'' {
+            return None;
+        }
+        match (self.chars.get(at + 1), self.chars.get(at + 2)) {
+            (Some('\\'), _) => Some(self.find_end(at + 3, "'")),
+            (Some(_), Some('\'')) => Some(at + 3),
+            // A lifetime
+            _ => Some(at + 1),
+        }
+    }
+
+    fn string_end(&self, at: usize) -> Option<usize> {
+        let quote = self.chars[at];
+        if self.syntax.raw_strings && quote == 'r' && (at == 0 || !self.is_ident_char(at - 1)) {
+            let hashes = self.chars[at + 1..]
+                .iter()
+                .take_while(|c| **c == '#')
+                .count();
+            if self.chars.get(at + 1 + hashes) == Some(&'"') {
+                let close = format!("\"{}", "#".repeat(hashes));
+                return Some(self.find_end(at + 2 + hashes, &close));
+            }
+        }
+        if !self.syntax.quotes.contains(&quote) {
+            return None;
+        }
+        let triple = quote.to_string().repeat(3);
+        if self.syntax.triple_quotes && self.starts_with(at, &triple) {
+            return Some(self.find_end(at + 3, &triple));
+        }
+        let mut k = at + 1;
+        while k < self.chars.len() {
+            match self.chars[k] {
+                '\\' => k += 2,
+                c if c == quote => return Some(k + 1),
+                _ => k += 1,
+            }
+        }
+        Some(self.chars.len())
+    }
+
+    /// Parses `<<EOF`, `<<-EOF`, `<<~EOF` and `<<'EOF'` markers,
+    /// returns the terminator and the index after the marker
+    fn heredoc_marker(&self, at: usize) -> Option<(String, usize)> {
+        if !self.syntax.heredocs || !self.starts_with(at, "<<") {
+            return None;
+        }
+        // `<<<` is a here-string
+        if self.chars.get(at + 2) == Some(&'<') || (at > 0 && self.chars[at - 1] == '<') {
+            return None;
+        }
+        let mut k = at + 2;
+        if matches!(self.chars.get(k), Some('-' | '~')) {
+            k += 1;
+        }
+        let quote = match self.chars.get(k) {
+            Some(q @ ('\'' | '"')) => {
+                k += 1;
+                Some(*q)
+            }
+            _ => None,
+        };
+        let start = k;
+        while self.is_ident_char(k) {
+            k += 1;
+        }
+        let terminator: String = self.chars[start..k].iter().collect();
+        if terminator.is_empty() {
+            return None;
+        }
+        match quote {
+            Some(q) if self.chars.get(k) == Some(&q) => Some((terminator, k + 1)),
+            Some(_) => None,
+            // Unquoted terminators are uppercase by convention, this avoids
+            // mistaking shifts such as `list <<value` for heredocs
+            None if terminator.starts_with(|c: char| c.is_uppercase()) => Some((terminator, k)),
+            None => None,
+        }
+    }
+
+    /// Parses the start of `#[name`, `#![name` and `@name(`,
+    /// returns the annotation and the index after its opening bracket
+    fn annotation_start(&self, at: usize) -> Option<(Annotation, usize)> {
+        let (name_start, open, close) = match self.chars[at] {
+            '#' if self.syntax.attributes => {
+                let bracket = if self.chars.get(at + 1) == Some(&'!') {
+                    at + 2
+                } else {
+                    at + 1
+                };
+                if self.chars.get(bracket) != Some(&'[') {
+                    return None;
+                }
+                (bracket + 1, '[', ']')
+            }
+            '@' if self.syntax.decorators => (at + 1, '(', ')'),
+            _ => return None,
+        };
+        let mut k = name_start;
+        while self.chars.get(k).is_some_and(|c| c.is_whitespace()) {
+            k += 1;
+        }
+        let path_start = k;
+        while self.is_ident_char(k) || matches!(self.chars.get(k), Some('.' | ':')) {
+            k += 1;
+        }
+        // Only the last segment of paths such as `serde::rename` or `app.route` matters
+        let path: String = self.chars[path_start..k].iter().collect();
+        let name = path.rsplit(['.', ':']).next()?;
+        if name.is_empty() {
+            return None;
+        }
+        let end = match open {
+            '[' => name_start,
+            _ if self.chars.get(k) == Some(&'(') => k + 1,
+            _ => return None,
+        };
+        let prose = self
+            .prose_annotations
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(name));
+        let annotation = Annotation {
+            prose,
+            open,
+            close,
+            depth: 1,
+        };
+        Some((annotation, end))
+    }
+
+    fn is_heredoc_terminator(&self, at: usize, terminator: &str) -> bool {
+        let line: String = self.chars[at..self.line_end(at)].iter().collect();
+        line.trim() == terminator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn scopes(language_id: &str, str: &str) -> Scopes {
+        let prose_annotations = vec!["doc".to_string(), "deprecated".to_string()];
+        Scopes::new(
+            Syntax::for_language(language_id).unwrap(),
+            str.chars(),
+            &prose_annotations,
+            true,
+            PositionEncoding::Utf16,
+        )
+    }
+
+    fn words_in_scope(language_id: &str, str: &str, scope: Scope) -> Vec<String> {
+        let scopes = scopes(language_id, str);
+        Lexer::new(str.chars())
+            .filter(|t| scopes.scope_of(&t.start) == scope)
+            .map(|t| t.lexeme)
+            .collect()
+    }
+
+    #[test]
+    fn it_finds_comments_and_strings() {
+        let str = "// a commnet\nlet greeting = \"helo\"; /* blck */";
+        assert_eq!(
+            words_in_scope("rust", str, Scope::Comment),
+            vec!["a", "commnet", "blck"]
+        );
+        assert_eq!(words_in_scope("rust", str, Scope::String), vec!["helo"]);
+        assert_eq!(
+            words_in_scope("rust", str, Scope::Code),
+            vec!["let", "greeting"]
+        );
+    }
+
+    #[test]
+    fn it_finds_multi_line_rust_raw_strings() {
+        let str = "let sql = r#\"\n  // teh \"quoted\" query\n\"#;\nlet after = 1;";
+        let scopes = scopes("rust", str);
+        assert_eq!(
+            scopes.scope_of(&Pos { line: 1, col: 5 }),
+            Scope::String,
+            "the comment-like word inside the raw string"
+        );
+        assert_eq!(
+            words_in_scope("rust", str, Scope::String),
+            vec!["r", "teh", "quoted", "query"]
+        );
+        assert_eq!(
+            words_in_scope("rust", str, Scope::Code),
+            vec!["let", "sql", "let", "after"]
+        );
+    }
+
+    #[test]
+    fn it_skips_rust_lifetimes_and_char_literals() {
+        let str = "fn first<'a>(s: &'a str) -> char { '\"' } // commnet";
+        assert_eq!(
+            words_in_scope("rust", str, Scope::String),
+            Vec::<String>::new()
+        );
+        assert_eq!(words_in_scope("rust", str, Scope::Comment), vec!["commnet"]);
+    }
+
+    #[test]
+    fn it_finds_shell_heredocs() {
+        let str = "cat <<EOF > out.txt\nsome teh text\nEOF\necho done";
+        assert_eq!(
+            words_in_scope("shellscript", str, Scope::String),
+            vec!["some", "teh", "text"]
+        );
+        assert_eq!(
+            words_in_scope("shellscript", str, Scope::Code),
+            vec!["cat", "EOF", "out", "txt", "EOF", "echo", "done"]
+        );
+    }
+
+    #[test]
+    fn it_finds_ruby_squiggly_heredocs() {
+        let str = "text = <<~'MSG'\n  Helo there\n  MSG\nputs text";
+        assert_eq!(
+            words_in_scope("ruby", str, Scope::String),
+            vec!["Helo", "there"]
+        );
+    }
+
+    #[test]
+    fn it_only_treats_hash_as_a_comment_at_word_start() {
+        let str = "echo ${#list} # commnet";
+        assert_eq!(
+            words_in_scope("shellscript", str, Scope::Comment),
+            vec!["commnet"]
+        );
+    }
+
+    #[test]
+    fn it_checks_prose_attribute_arguments() {
+        let str = "#[doc = \"teh docs\"]\n#![deprecated(note = \"use teh other\")]\n#[serde(rename = \"fooBaar\")]\nfn main() {}";
+        assert_eq!(
+            words_in_scope("rust", str, Scope::String),
+            vec!["teh", "docs", "use", "teh", "other"]
+        );
+        assert_eq!(
+            words_in_scope("rust", str, Scope::Code),
+            vec![
+                "doc",
+                "deprecated",
+                "note",
+                "serde",
+                "rename",
+                "fooBaar",
+                "fn",
+                "main"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_checks_prose_decorator_arguments() {
+        let str =
+            "@Deprecated(\"use teh other\")\n@Component({ selector: \"app-roott\" })\nclass Foo {}";
+        assert_eq!(
+            words_in_scope("typescript", str, Scope::String),
+            vec!["use", "teh", "other"]
+        );
+        assert_eq!(
+            words_in_scope("typescript", str, Scope::Code),
+            vec![
+                "Deprecated",
+                "Component",
+                "selector",
+                "app",
+                "roott",
+                "class",
+                "Foo"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_uses_the_last_segment_of_decorator_paths() {
+        let str =
+            "@app.route(\"/abuot\")\n@warnings.deprecated(\"use teh other\")\ndef about(): pass";
+        assert_eq!(
+            words_in_scope("python", str, Scope::String),
+            vec!["use", "teh", "other"]
+        );
+    }
+
+    #[test]
+    fn it_skips_format_placeholders_in_strings() {
+        let str = "format!(\"recieved {count} items {} in {0:>8}, {{escaped}}\", n)";
+        assert_eq!(
+            words_in_scope("rust", str, Scope::String),
+            vec!["recieved", "items", "in", "escaped"]
+        );
+        let str = "print(\"%s recieved %(count)d itmes, 100%% {name!r}\" % args)";
+        assert_eq!(
+            words_in_scope("python", str, Scope::String),
+            vec!["recieved", "itmes"]
+        );
+    }
+}
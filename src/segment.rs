@@ -0,0 +1,83 @@
+/// Words longer than this are not segmented, every candidate segment
+/// costs a round-trip to the spell checker.
+const MAX_WORD_LEN: usize = 30;
+/// Single letters are accepted by most dictionaries and would make almost
+/// anything segmentable.
+const MIN_SEGMENT_LEN: usize = 2;
+
+/// Splits a run-together word such as `thequickbrown` into the fewest
+/// dictionary words, returns `None` if it doesn't split into at least two.
+pub fn segment<F: Fn(&str) -> bool>(word: &str, is_word: F) -> Option<Vec<String>> {
+    let chars: Vec<char> = word.chars().collect();
+    if chars.len() > MAX_WORD_LEN || chars.len() < 2 * MIN_SEGMENT_LEN {
+        return None;
+    }
+
+    // best[i] is the start of the last segment of the best split of chars[..i]
+    // together with the number of segments in that split
+    let mut best: Vec<Option<(usize, usize)>> = vec![None; chars.len() + 1];
+    best[0] = Some((0, 0));
+    for end in MIN_SEGMENT_LEN..=chars.len() {
+        for start in 0..=end - MIN_SEGMENT_LEN {
+            let Some((_, count)) = best[start] else {
+                continue;
+            };
+            if best[end].is_some_and(|(_, c)| c <= count + 1) {
+                continue;
+            }
+            let candidate: String = chars[start..end].iter().collect();
+            if is_word(&candidate) {
+                best[end] = Some((start, count + 1));
+            }
+        }
+    }
+
+    let (_, count) = best[chars.len()]?;
+    if count < 2 {
+        return None;
+    }
+    let mut segments = vec![];
+    let mut end = chars.len();
+    while end > 0 {
+        let (start, _) = best[end]?;
+        segments.push(chars[start..end].iter().collect());
+        end = start;
+    }
+    segments.reverse();
+    Some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_word(word: &str) -> bool {
+        ["the", "quick", "brown", "theme", "me", "fox"].contains(&word)
+    }
+
+    #[test]
+    fn it_splits_run_together_words() {
+        assert_eq!(
+            segment("thequickbrown", is_word),
+            Some(vec![
+                "the".to_string(),
+                "quick".to_string(),
+                "brown".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn it_prefers_the_fewest_segments() {
+        assert_eq!(
+            segment("themefox", is_word),
+            Some(vec!["theme".to_string(), "fox".to_string()])
+        );
+    }
+
+    #[test]
+    fn it_rejects_words_that_do_not_split() {
+        assert_eq!(segment("thequikbrown", is_word), None);
+        assert_eq!(segment("quick", is_word), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::lexer::{Location, Pos, Token};
+use crate::position::PositionEncoding;
+
+/// A kind of text that isn't prose although the lexer finds words in it,
+/// e.g. urls or hashes. It is looked for in runs of the characters it can be
+/// made of.
+pub struct SkippedKind {
+    /// Characters a run is made of, e.g. anything but whitespace
+    pub is_part: fn(char) -> bool,
+    /// The spans of this kind within a run
+    pub spans_in: fn(&Run) -> Vec<Location>,
+}
+
+/// Consecutive characters of a single kind with their positions
+pub struct Run<'a> {
+    pub chars: &'a [(char, Pos)],
+    /// Position after the last character
+    pub end: Pos,
+}
+
+impl<'a> Run<'a> {
+    fn new(chars: &'a [(char, Pos)], end: Pos) -> Self {
+        Self { chars, end }
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().map(|(c, _)| c).collect()
+    }
+
+    /// Location of the characters from `start` up to `stop`, exclusive
+    pub fn location(&self, start: usize, stop: usize) -> Location {
+        let end = self.chars.get(stop).map_or(self.end, |(_, pos)| *pos);
+        Location::new(self.chars[start].1, end)
+    }
+}
+
+/// Runs of non-whitespace characters, i.e. what reads as a single word
+pub fn is_not_whitespace(char: char) -> bool {
+    !char.is_whitespace()
+}
+
+/// Spans of a document the spell-check skips. Every kind is looked for in
+/// a single pass over the text, which tracks the positions for all of them.
+#[derive(Default)]
+pub struct SkippedSpans(Vec<Location>);
+
+impl SkippedSpans {
+    #[cfg(test)]
+    pub fn new<I: Iterator<Item = char>>(
+        text: I,
+        encoding: PositionEncoding,
+        kinds: &[&SkippedKind],
+    ) -> Self {
+        Self::starting_at(text, Pos::start(), encoding, kinds)
+    }
+
+    /// Spans of `text` found at `start` of a document, e.g. a single line
+    pub fn starting_at<I: Iterator<Item = char>>(
+        text: I,
+        start: Pos,
+        encoding: PositionEncoding,
+        kinds: &[&SkippedKind],
+    ) -> Self {
+        let mut spans = vec![];
+        let mut runs: Vec<Vec<(char, Pos)>> = vec![vec![]; kinds.len()];
+        let mut pos = start;
+
+        for char in text {
+            for (kind, run) in kinds.iter().zip(&mut runs) {
+                if (kind.is_part)(char) {
+                    run.push((char, pos));
+                } else if !run.is_empty() {
+                    spans.extend((kind.spans_in)(&Run::new(run, pos)));
+                    run.clear();
+                }
+            }
+
+            if char == '\n' {
+                pos = Pos {
+                    line: pos.line + 1,
+                    col: 0,
+                };
+            } else {
+                pos = pos.set_col(pos.col + encoding.char_len(char));
+            }
+        }
+        for (kind, run) in kinds.iter().zip(&runs) {
+            if !run.is_empty() {
+                spans.extend((kind.spans_in)(&Run::new(run, pos)));
+            }
+        }
+
+        Self(spans)
+    }
+
+    pub fn contains(&self, token: &Token) -> bool {
+        self.0.iter().any(|l| l.contains(&token.start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    const SHOUTING: SkippedKind = SkippedKind {
+        is_part: char::is_alphabetic,
+        spans_in: shouting_in,
+    };
+
+    fn shouting_in(run: &Run) -> Vec<Location> {
+        let shouting = run.chars.iter().all(|(c, _)| c.is_uppercase());
+        shouting
+            .then(|| run.location(0, run.chars.len()))
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn it_tracks_positions_across_lines_and_encodings() {
+        let str = "🤖 LOUD quiet\nmore NOISE";
+        let spans = SkippedSpans::new(str.chars(), PositionEncoding::Utf16, &[&SHOUTING]);
+        assert_eq!(
+            spans.0,
+            vec![
+                Location::new(Pos { line: 0, col: 3 }, Pos { line: 0, col: 7 }),
+                Location::new(Pos { line: 1, col: 5 }, Pos { line: 1, col: 10 }),
+            ]
+        );
+        let checked = Lexer::new(str.chars())
+            .filter(|t| !spans.contains(t))
+            .map(|t| t.lexeme)
+            .collect::<Vec<_>>();
+        assert_eq!(checked, vec!["quiet", "more"]);
+    }
+
+    #[test]
+    fn it_finds_spans_of_a_line() {
+        let line = "more NOISE";
+        let spans = SkippedSpans::starting_at(
+            line.chars(),
+            Pos { line: 1, col: 0 },
+            PositionEncoding::Utf16,
+            &[&SHOUTING],
+        );
+        assert_eq!(
+            spans.0,
+            vec![Location::new(
+                Pos { line: 1, col: 5 },
+                Pos { line: 1, col: 10 }
+            )]
+        );
+    }
+}
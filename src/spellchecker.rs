@@ -0,0 +1,39 @@
+use crate::config::DictionaryPath;
+use hunspell_rs::{CheckResult, Hunspell};
+
+/// An engine checking words against a single dictionary. Engines are shared
+/// by the checker threads, which take turns using each of them.
+pub trait SpellChecker: Send {
+    fn check(&self, word: &str) -> bool;
+    fn suggest(&self, word: &str) -> Vec<String>;
+}
+
+pub struct HunspellChecker(Hunspell);
+
+// Hunspell holds a raw pointer, moving it between threads is fine as long as
+// only one uses it at a time, which the lock of each checker ensures
+unsafe impl Send for HunspellChecker {}
+
+impl HunspellChecker {
+    pub fn new(path: &DictionaryPath) -> Self {
+        Self(Hunspell::new(
+            path.aff.to_str().unwrap(),
+            path.dic.to_str().unwrap(),
+        ))
+    }
+}
+
+impl SpellChecker for HunspellChecker {
+    fn check(&self, word: &str) -> bool {
+        self.0.check(word) == CheckResult::FoundInDictionary
+    }
+
+    fn suggest(&self, word: &str) -> Vec<String> {
+        self.0.suggest(word)
+    }
+}
+
+/// The engine for the dictionary files at `path`
+pub fn new_spellchecker(path: &DictionaryPath) -> Box<dyn SpellChecker> {
+    Box::new(HunspellChecker::new(path))
+}
@@ -0,0 +1,230 @@
+use crate::config::{ProperNounSuggestions, SuggestionTiebreak};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Levenshtein distance where swapping two adjacent characters counts as a
+/// single edit, since transpositions are among the most common typos.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            dist[i][j] = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist[i][j] = dist[i][j].min(dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    dist[a.len()][b.len()]
+}
+
+/// Applies the casing of `original` to `replacement`, e.g. `Recieve` is
+/// replaced with `Receive` and `RECIEVE` with `RECEIVE`. Lowercase and mixed
+/// case originals keep the replacement as is, so proper nouns stay capitalized.
+pub fn match_case(original: &str, replacement: &str) -> String {
+    let mut letters = original.chars().filter(|c| c.is_alphabetic());
+    let Some(first) = letters.next() else {
+        return replacement.to_string();
+    };
+    let rest = letters.collect::<Vec<_>>();
+    if first.is_uppercase() && !rest.is_empty() && rest.iter().all(|c| c.is_uppercase()) {
+        return replacement.to_uppercase();
+    }
+    if first.is_uppercase() && rest.iter().all(|c| c.is_lowercase()) {
+        let mut chars = replacement.chars();
+        return match chars.next() {
+            Some(c) => c.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        };
+    }
+    replacement.to_string()
+}
+
+/// Orders suggestions so the most likely fix comes first
+pub struct SuggestionRanker {
+    tiebreak: SuggestionTiebreak,
+    // Word -> position in the frequency list, lower is more frequent
+    frequencies: HashMap<String, usize>,
+    proper_nouns: ProperNounSuggestions,
+}
+
+impl SuggestionRanker {
+    /// `frequency_list` contains one word per line, most frequent first
+    pub fn new(tiebreak: SuggestionTiebreak, frequency_list: &str) -> Self {
+        let mut frequencies = HashMap::new();
+        for (rank, word) in frequency_list
+            .lines()
+            .map(|l| l.trim().to_lowercase())
+            .filter(|w| !w.is_empty())
+            .enumerate()
+        {
+            frequencies.entry(word).or_insert(rank);
+        }
+        Self {
+            tiebreak,
+            frequencies,
+            proper_nouns: ProperNounSuggestions::Keep,
+        }
+    }
+
+    pub fn proper_nouns(mut self, proper_nouns: ProperNounSuggestions) -> Self {
+        self.proper_nouns = proper_nouns;
+        self
+    }
+
+    /// Removes duplicates and sorts by edit distance to `word`, then by how
+    /// close their length is to it, breaking the remaining ties according to
+    /// the configured tiebreak.
+    pub fn rank(&self, word: &str, suggestions: Vec<String>) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let len = word.chars().count();
+        let lowercase = word.chars().next().is_some_and(|c| c.is_lowercase());
+        let is_proper_noun =
+            |s: &str| lowercase && s.chars().next().is_some_and(|c| c.is_uppercase());
+        let mut ranked = suggestions
+            .into_iter()
+            .filter(|s| seen.insert(s.clone()))
+            .filter(|s| self.proper_nouns != ProperNounSuggestions::Remove || !is_proper_noun(s))
+            .map(|s| {
+                let demoted =
+                    self.proper_nouns == ProperNounSuggestions::Demote && is_proper_noun(&s);
+                let len_diff = s.chars().count().abs_diff(len);
+                (demoted, edit_distance(word, &s), len_diff, s)
+            })
+            .collect::<Vec<_>>();
+        // The sort is stable so equal elements keep the hunspell order
+        ranked.sort_by(
+            |(a_demoted, a_dist, a_len_diff, a), (b_demoted, b_dist, b_len_diff, b)| {
+                a_demoted
+                    .cmp(b_demoted)
+                    .then(a_dist.cmp(b_dist))
+                    .then(a_len_diff.cmp(b_len_diff))
+                    .then_with(|| self.tiebreak(a, b))
+            },
+        );
+        ranked.into_iter().map(|(_, _, _, s)| s).collect()
+    }
+
+    fn tiebreak(&self, a: &str, b: &str) -> Ordering {
+        match self.tiebreak {
+            SuggestionTiebreak::Original => Ordering::Equal,
+            SuggestionTiebreak::Alphabetical => a.cmp(b),
+            SuggestionTiebreak::Frequency => {
+                let rank = |w: &str| {
+                    self.frequencies
+                        .get(&w.to_lowercase())
+                        .copied()
+                        .unwrap_or(usize::MAX)
+                };
+                // Words missing from the frequency list fall back to alphabetical order
+                rank(a).cmp(&rank(b)).then_with(|| a.cmp(b))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn it_computes_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("frmo", "from"), 1);
+        assert_eq!(edit_distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn it_ranks_by_edit_distance() {
+        let ranker = SuggestionRanker::new(SuggestionTiebreak::Original, "");
+        let ranked = ranker.rank("recieve", words(&["receiver", "receive", "receive"]));
+        assert_eq!(ranked, words(&["receive", "receiver"]));
+    }
+
+    #[test]
+    fn it_prefers_suggestions_of_a_similar_length() {
+        let ranker = SuggestionRanker::new(SuggestionTiebreak::Original, "");
+        assert_eq!(
+            ranker.rank("teh", words(&["tech", "the", "ten"])),
+            words(&["the", "ten", "tech"])
+        );
+    }
+
+    #[test]
+    fn it_breaks_ties_by_strategy() {
+        let suggestions = words(&["cut", "cot", "cat"]);
+
+        let ranker = SuggestionRanker::new(SuggestionTiebreak::Original, "");
+        assert_eq!(ranker.rank("cst", suggestions.clone()), suggestions);
+
+        let ranker = SuggestionRanker::new(SuggestionTiebreak::Alphabetical, "");
+        assert_eq!(
+            ranker.rank("cst", suggestions.clone()),
+            words(&["cat", "cot", "cut"])
+        );
+
+        let ranker = SuggestionRanker::new(SuggestionTiebreak::Frequency, "cut\ncat\n");
+        assert_eq!(
+            ranker.rank("cst", suggestions),
+            words(&["cut", "cat", "cot"])
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_alphabetical_without_frequency_list() {
+        let ranker = SuggestionRanker::new(SuggestionTiebreak::Frequency, "");
+        assert_eq!(
+            ranker.rank("cst", words(&["cut", "cot", "cat"])),
+            words(&["cat", "cot", "cut"])
+        );
+    }
+
+    #[test]
+    fn it_handles_proper_nouns_for_lowercase_words() {
+        let suggestions = words(&["Tex", "tax", "text"]);
+        let ranker = SuggestionRanker::new(SuggestionTiebreak::Original, "");
+        assert_eq!(ranker.rank("tex", suggestions.clone()), suggestions);
+
+        let ranker = ranker.proper_nouns(ProperNounSuggestions::Demote);
+        assert_eq!(
+            ranker.rank("tex", suggestions.clone()),
+            words(&["tax", "text", "Tex"])
+        );
+        // Capitalized words keep their capitalized suggestions
+        assert_eq!(
+            ranker.rank("Tex", suggestions.clone()),
+            words(&["Tex", "tax", "text"])
+        );
+
+        let ranker = ranker.proper_nouns(ProperNounSuggestions::Remove);
+        assert_eq!(ranker.rank("tex", suggestions), words(&["tax", "text"]));
+    }
+
+    #[test]
+    fn it_matches_the_case_of_the_original() {
+        assert_eq!(match_case("recieve", "receive"), "receive");
+        assert_eq!(match_case("Recieve", "receive"), "Receive");
+        assert_eq!(match_case("RECIEVE", "receive"), "RECEIVE");
+        assert_eq!(match_case("kubernets", "Kubernetes"), "Kubernetes");
+        assert_eq!(match_case("iPhon", "iPhone"), "iPhone");
+    }
+}
@@ -0,0 +1,163 @@
+use crate::config::DictionaryPath;
+use crate::spellchecker::SpellChecker;
+use crate::suggestions::edit_distance;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// Suggestions are at most this many edits away from the misspelling
+const MAX_DISTANCE: usize = 2;
+/// Only deletes of the first characters are indexed which keeps the index
+/// small, the whole word is compared when looking up candidates
+const PREFIX_LENGTH: usize = 7;
+
+/// Symmetric delete suggester, words sharing a string after deleting up to
+/// `MAX_DISTANCE` characters from each are candidates for each other.
+/// Much faster than Hunspell's `suggest` but it doesn't know about affixes.
+pub struct SymSpell {
+    words: Vec<String>,
+    // Indices into `words` by the deletes of their lowercase prefix
+    deletes: HashMap<String, Vec<usize>>,
+}
+
+impl SymSpell {
+    pub fn new(words: impl IntoIterator<Item = String>) -> Self {
+        let mut words = words.into_iter().collect::<Vec<_>>();
+        words.sort();
+        words.dedup();
+        let mut deletes: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, word) in words.iter().enumerate() {
+            for delete in deletes_of(&prefix(&word.to_lowercase())) {
+                deletes.entry(delete).or_default().push(i);
+            }
+        }
+        Self { words, deletes }
+    }
+
+    /// The stems of the `.dic` files of the dictionaries, files that can't
+    /// be read are skipped
+    pub fn from_dictionaries(paths: &[DictionaryPath]) -> Self {
+        let words = paths
+            .iter()
+            .filter_map(|p| fs::read_to_string(&p.dic).ok())
+            .flat_map(|content| {
+                content
+                    .lines()
+                    // The first line is the word count
+                    .skip(1)
+                    .filter_map(dic_word)
+                    .collect::<Vec<_>>()
+            });
+        Self::new(words)
+    }
+}
+
+impl SpellChecker for SymSpell {
+    fn check(&self, word: &str) -> bool {
+        self.words
+            .binary_search_by(|w| w.as_str().cmp(word))
+            .is_ok()
+    }
+
+    /// Closest words first, ties are sorted alphabetically
+    fn suggest(&self, word: &str) -> Vec<String> {
+        let input = word.to_lowercase();
+        let input_len = input.chars().count();
+        let mut seen = HashSet::new();
+        let mut candidates = vec![];
+        for delete in deletes_of(&prefix(&input)) {
+            let Some(indices) = self.deletes.get(&delete) else {
+                continue;
+            };
+            for &i in indices {
+                if !seen.insert(i) {
+                    continue;
+                }
+                let candidate = &self.words[i];
+                if candidate.chars().count().abs_diff(input_len) > MAX_DISTANCE {
+                    continue;
+                }
+                let distance = edit_distance(&candidate.to_lowercase(), &input);
+                if distance <= MAX_DISTANCE && candidate != word {
+                    candidates.push((distance, candidate));
+                }
+            }
+        }
+        candidates.sort();
+        candidates.into_iter().map(|(_, w)| w.to_string()).collect()
+    }
+}
+
+/// The word of a `.dic` line without its affix flags and morphology
+fn dic_word(line: &str) -> Option<String> {
+    let entry = line.split_whitespace().next()?;
+    let word = entry.split('/').next()?;
+    (!word.is_empty()).then(|| word.to_string())
+}
+
+fn prefix(word: &str) -> String {
+    word.chars().take(PREFIX_LENGTH).collect()
+}
+
+/// `word` and every string left after deleting up to `MAX_DISTANCE` of its characters
+fn deletes_of(word: &str) -> HashSet<String> {
+    let mut deletes = HashSet::from([word.to_string()]);
+    let mut edge = vec![word.to_string()];
+    for _ in 0..MAX_DISTANCE {
+        let mut next = vec![];
+        for word in &edge {
+            let chars = word.chars().collect::<Vec<_>>();
+            for i in 0..chars.len() {
+                let delete = chars[..i].iter().chain(&chars[i + 1..]).collect::<String>();
+                if deletes.insert(delete.clone()) {
+                    next.push(delete);
+                }
+            }
+        }
+        edge = next;
+    }
+    deletes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symspell(words: &[&str]) -> SymSpell {
+        SymSpell::new(words.iter().map(|w| w.to_string()))
+    }
+
+    #[test]
+    fn it_suggests_the_closest_words_first() {
+        let symspell = symspell(&["world", "would", "word", "wild", "kubernetes"]);
+        assert_eq!(
+            symspell.suggest("wrold"),
+            vec!["world", "wild", "word", "would"]
+        );
+        assert_eq!(symspell.suggest("kubernets"), vec!["kubernetes"]);
+        assert!(symspell.suggest("xyzzy").is_empty());
+    }
+
+    #[test]
+    fn it_finds_words_differing_after_the_prefix() {
+        let symspell = symspell(&["internationalization"]);
+        assert_eq!(
+            symspell.suggest("internationalisaton"),
+            vec!["internationalization"]
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_casing_of_dictionary_words() {
+        let symspell = symspell(&["Paris", "parish"]);
+        assert_eq!(symspell.suggest("paris"), vec!["Paris", "parish"]);
+        assert!(symspell.check("Paris"));
+        assert!(!symspell.check("Pariss"));
+    }
+
+    #[test]
+    fn it_reads_words_of_dic_lines() {
+        assert_eq!(dic_word("hello/MS"), Some("hello".to_string()));
+        assert_eq!(dic_word("world\tpo:noun"), Some("world".to_string()));
+        assert_eq!(dic_word(""), None);
+    }
+}
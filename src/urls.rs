@@ -0,0 +1,124 @@
+use crate::lexer::Location;
+use crate::skipped_spans::{is_not_whitespace, Run, SkippedKind};
+
+/// URLs such as `https://example.com/foo-bar` and email addresses.
+/// The lexer drops the punctuation that gives them away so these would
+/// otherwise turn into tokens like `https`, `example` and `com`.
+pub const URLS: SkippedKind = SkippedKind {
+    is_part: is_not_whitespace,
+    spans_in: url_in,
+};
+
+/// The location of the URL or email address in a run of non-whitespace
+/// characters, if any
+fn url_in(run: &Run) -> Vec<Location> {
+    let chars = run.chars.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+    url_bounds(&chars)
+        .or_else(|| email_bounds(&chars))
+        .map(|(start, stop)| run.location(start, stop))
+        .into_iter()
+        .collect()
+}
+
+/// From the scheme of `scheme://...` or from `www.` up to a quote or angle
+/// bracket, without the punctuation after it
+fn url_bounds(chars: &[char]) -> Option<(usize, usize)> {
+    let start = match chars.windows(3).position(|w| w == [':', '/', '/']) {
+        Some(separator) => {
+            let scheme = chars[..separator]
+                .iter()
+                .rev()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+                .count();
+            (scheme > 0).then_some(separator - scheme)?
+        }
+        None => {
+            let start = chars.iter().position(|c| c.is_alphanumeric())?;
+            chars[start..]
+                .starts_with(&['w', 'w', 'w', '.'])
+                .then_some(start)?
+        }
+    };
+    let mut stop = start
+        + chars[start..]
+            .iter()
+            .take_while(|c| !matches!(c, '"' | '\'' | '`' | '<' | '>'))
+            .count();
+    while stop > start
+        && matches!(
+            chars[stop - 1],
+            ')' | ']' | '}' | ',' | ';' | ':' | '!' | '?' | '.'
+        )
+    {
+        stop -= 1;
+    }
+    (start < stop).then_some((start, stop))
+}
+
+/// `local@domain.tld` around the first `@`, mentions like `@user` aren't
+/// addresses
+fn email_bounds(chars: &[char]) -> Option<(usize, usize)> {
+    let at = chars.iter().position(|c| *c == '@')?;
+    let local = chars[..at]
+        .iter()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '%' | '+' | '-'))
+        .count();
+    let domain = chars[at + 1..]
+        .iter()
+        .take_while(|c| c.is_alphanumeric() || matches!(c, '.' | '-'))
+        .count();
+    // A period after the address ends the sentence
+    let domain = chars[at + 1..at + 1 + domain]
+        .iter()
+        .collect::<String>()
+        .trim_end_matches('.')
+        .to_string();
+    let is_domain = domain.contains('.') && domain.split('.').all(|part| !part.is_empty());
+    (local > 0 && is_domain).then_some((at - local, at + 1 + domain.chars().count()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::position::PositionEncoding;
+    use crate::skipped_spans::SkippedSpans;
+
+    fn checked_words(str: &str) -> String {
+        let urls = SkippedSpans::new(str.chars(), PositionEncoding::Utf16, &[&URLS]);
+        Lexer::new(str.chars())
+            .filter(|t| !urls.contains(t))
+            .map(|t| t.lexeme)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn it_skips_urls() {
+        assert_eq!(
+            checked_words("// see https://example.com/foo-bar?q=helo for more"),
+            "see for more"
+        );
+        assert_eq!(checked_words("Visit www.exampel.org."), "Visit");
+        assert_eq!(
+            checked_words("<a href=\"https://exampel.com\">link</a>"),
+            "a href link a"
+        );
+    }
+
+    #[test]
+    fn it_skips_email_addresses() {
+        assert_eq!(checked_words("Write to jane.doe@exampel.com."), "Write to");
+        assert_eq!(checked_words("(mailto:helo@wrold.io)"), "mailto");
+    }
+
+    #[test]
+    fn it_keeps_words_that_only_look_like_parts_of_urls() {
+        assert_eq!(
+            checked_words("ask @maintainer at the meeting: tomorrow"),
+            "ask maintainer at the meeting tomorrow"
+        );
+        assert_eq!(checked_words("user@localhost"), "user localhost");
+    }
+}
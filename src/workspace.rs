@@ -0,0 +1,52 @@
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/// Every file below `root`. With `respect_gitignore` files excluded by
+/// `.gitignore` or `.ignore` files are skipped, also outside of git repos.
+pub fn files(root: &Path, respect_gitignore: bool) -> Vec<PathBuf> {
+    WalkBuilder::new(root)
+        .git_ignore(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .git_global(respect_gitignore)
+        .ignore(respect_gitignore)
+        .require_git(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn file_names(root: &Path, respect_gitignore: bool) -> Vec<String> {
+        let mut names = files(root, respect_gitignore)
+            .iter()
+            .map(|p| p.strip_prefix(root).unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn it_skips_gitignored_files_unless_disabled() {
+        let root = std::env::temp_dir().join("rustproof-workspace-scan");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("notes.md"), "helo").unwrap();
+        fs::write(root.join("debug.log"), "wrold").unwrap();
+        fs::write(root.join("target").join("generated.rs"), "wrold").unwrap();
+
+        assert_eq!(file_names(&root, true), vec!["notes.md"]);
+        // Hidden files like .gitignore are always skipped
+        assert_eq!(
+            file_names(&root, false),
+            vec!["debug.log", "notes.md", "target/generated.rs"]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}